@@ -20,7 +20,7 @@ async fn main() -> Result<()> {
     let cli = Cli::parse();
     let elf = fs::read(cli.elf)?;
 
-    let app = App::new(&elf, None);
+    let app = App::new(&elf, None)?;
     let app_id = app.app_id;
 
     info!("Generated app_id: 0x{app_id}");
@@ -28,7 +28,7 @@ async fn main() -> Result<()> {
         None
     };
 
-    let app = App::new(&elf, None);
+    let app = App::new(&elf, None)?;
     let info = estimate_cost(app.program, app.pk, app.vk, inputs.as_deref(), None, false)?;
 
     let cycles = info.total_cycles;
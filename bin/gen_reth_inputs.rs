@@ -103,7 +103,7 @@ fn generate_pv_digest(elf_file_path: &Path, inputs: &[u8]) -> Result<String> {
     // read reth elf
     let elf = fs::read(elf_file_path)?;
 
-    let app = App::new(&elf, None);
+    let app = App::new(&elf, None)?;
 
     let info = estimate_cost(app.program, app.pk, app.vk, Some(inputs), None, false)?;
     let pv_digest = info.pv_digest;
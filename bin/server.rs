@@ -2,27 +2,30 @@ use anyhow::Result;
 use clap::Parser;
 use dotenvy::dotenv;
 use pico_proving_service::{
+    app_manager::{AppCache, AppManager},
     config::ServiceConfig,
     grpc::GrpcService,
-    proving::onchain::start_onchain_daemon,
-    proving_queue::{ProvingOutputs, ProvingQueue},
+    proving::{ProverPool, onchain::OnchainProver, worker::service::WorkerService},
+    proving_queue::{ProvingOutputs, ProvingQueue, TaskCancellations, restore_tasks},
+    scheduler::TaskScheduler,
+    task_manager::{EVENT_BROADCAST_CAPACITY, TaskManager},
 };
 use pico_vm::{
     iter::{ThreadPoolBuilder, current_num_threads},
     machine::logger::setup_logger,
-    thread::channel::SingleUnboundedChannel,
 };
 use sqlx::sqlite::SqlitePoolOptions;
-use std::{process::exit, sync::Arc};
-use tokio::signal::ctrl_c;
-use tracing::info;
+use std::{process::exit, sync::Arc, time::Duration};
+use tokio::{
+    signal::ctrl_c,
+    sync::{broadcast, mpsc::unbounded_channel},
+};
+use tracing::{info, warn};
 
 #[tokio::main]
 async fn main() -> Result<()> {
     dotenv().ok();
     setup_logger();
-    // Start background docker monitor for on-chain prover
-    start_onchain_daemon();
 
     ThreadPoolBuilder::new()
         .num_threads(num_cpus::get())
@@ -35,23 +38,64 @@ async fn main() -> Result<()> {
 
     let db_pool = Arc::new(SqlitePoolOptions::new().connect(&cfg.db_url).await?);
     let proving_outputs = Arc::new(ProvingOutputs::default());
-    let grpc_to_proving_channel = SingleUnboundedChannel::default();
+    let cancellations = Arc::new(TaskCancellations::default());
+    let scheduler = Arc::new(TaskScheduler::default());
+    let (event_sender, event_receiver) = unbounded_channel();
+    let (event_broadcaster, _) = broadcast::channel(EVENT_BROADCAST_CAPACITY);
+
+    let app_cache = Arc::new(AppCache::new(cfg.app_cache_size * 1024 * 1024));
+    let app_manager = AppManager::new(db_pool.clone(), app_cache);
 
     let mut handles = vec![];
 
+    let task_manager = TaskManager::new(db_pool.clone());
+    handles.push(task_manager.run(event_receiver, event_broadcaster.clone()));
+
+    // the gnark docker container is only monitored if it's managed by the service
+    let onchain = OnchainProver::new(cfg.gnark_config())?;
+    onchain.start_docker_daemon();
+    if !onchain.ready().await {
+        warn!("gnark server {} is not ready", cfg.gnark_url);
+    }
+
+    // provers are shared by the concurrent tasks and the remote workers
+    let prover_pool = Arc::new(ProverPool::new(
+        cfg.prover_count,
+        cfg.chunk_retry_count,
+        Duration::from_secs(cfg.worker_heartbeat_timeout),
+        Duration::from_secs(cfg.task_lease_timeout),
+        cfg.recursion_keys_dir.clone(),
+        onchain,
+    ));
+    if let Some(addr) = cfg.worker_grpc_addr {
+        let worker_service = WorkerService::new(cfg.clone(), app_manager.clone(), &prover_pool);
+        handles.push(worker_service.run(addr));
+    }
+
     let proving_queue = ProvingQueue::new(
         cfg.clone(),
         proving_outputs.clone(),
-        grpc_to_proving_channel.receiver(),
+        scheduler.clone(),
         db_pool.clone(),
+        event_sender.clone(),
+        cancellations.clone(),
+        prover_pool,
     );
     handles.push(proving_queue.run());
 
+    // re-enqueue the tasks interrupted by the last shutdown
+    let restored = restore_tasks(db_pool.clone(), &app_manager, &scheduler).await?;
+    info!("restored {restored} unfinished proving tasks");
+
     let grpc_service = GrpcService::new(
         cfg,
         db_pool,
+        app_manager,
         proving_outputs,
-        grpc_to_proving_channel.sender(),
+        scheduler,
+        cancellations,
+        event_sender,
+        event_broadcaster,
     );
     handles.push(grpc_service.run());
 
@@ -2,7 +2,10 @@ use anyhow::Result;
 use clap::{Args, Parser, Subcommand};
 use dotenvy::dotenv;
 use pico_proving_service::{
-    EstimateCostRequest, GetProvingResultRequest, ProveTaskRequest, RegisterAppRequest,
+    CancelTaskRequest, DeleteAppRequest, EstimateCostRequest, GetAppRequest,
+    GetIntermediateProofRequest, GetProvingResultRequest, GetTaskStatusRequest,
+    GetVerificationParamsRequest, ListAppsRequest, ProofFormat, ProofStage, ProveTaskRequest,
+    RegisterAppRequest, UpdateAppInfoRequest, VerifyProofRequest, WatchTaskRequest, WrapType,
     prover_network_client::ProverNetworkClient,
 };
 use pico_vm::machine::logger::setup_logger;
@@ -37,6 +40,24 @@ enum Command {
     #[command(about = "Register a new application with build elf")]
     RegisterApp(RegisterAppCommand),
 
+    #[command(about = "Fetch the registration status and metadata of an application")]
+    GetApp(GetAppCommand),
+
+    #[command(about = "List the registered applications")]
+    ListApps(ListAppsCommand),
+
+    #[command(about = "Update the program information of an application")]
+    UpdateAppInfo(UpdateAppInfoCommand),
+
+    #[command(about = "Delete an application without pending tasks")]
+    DeleteApp(DeleteAppCommand),
+
+    #[command(about = "Fetch the parameters for verifying the proofs of an application on-chain")]
+    GetVerificationParams(GetVerificationParamsCommand),
+
+    #[command(about = "Verify a Groth16 proof with the public values of an application")]
+    VerifyProof(VerifyProofCommand),
+
     #[command(about = "Estimate gas cost for an application")]
     EstimateCost(EstimateCostCommand),
 
@@ -45,6 +66,18 @@ enum Command {
 
     #[command(about = "Fetch the proving result if complete")]
     GetProvingResult(GetProvingResultCommand),
+
+    #[command(about = "Download a persisted compress or embed proof of a proved task")]
+    GetIntermediateProof(GetIntermediateProofCommand),
+
+    #[command(about = "Fetch the status and progress of a proving task")]
+    GetTaskStatus(GetTaskStatusCommand),
+
+    #[command(about = "Cancel a queued or in-progress proving task")]
+    CancelTask(CancelTaskCommand),
+
+    #[command(about = "Watch the progress events of a proving task until it finishes")]
+    WatchTask(WatchTaskCommand),
 }
 
 #[derive(Args)]
@@ -56,6 +89,67 @@ struct RegisterAppCommand {
     info: Option<String>,
 }
 
+#[derive(Args)]
+struct GetAppCommand {
+    #[arg(
+        long,
+        required_unless_present = "registration_id",
+        help = "Application unique ID"
+    )]
+    app_id: Option<String>,
+
+    #[arg(long, help = "Registration ID returned by RegisterApp")]
+    registration_id: Option<String>,
+}
+
+#[derive(Args)]
+struct ListAppsCommand {
+    #[arg(long, help = "Number of applications to skip")]
+    offset: Option<u64>,
+
+    #[arg(long, help = "Maximum number of applications to return")]
+    limit: Option<u64>,
+}
+
+#[derive(Args)]
+struct UpdateAppInfoCommand {
+    #[arg(long, help = "Application unique ID")]
+    app_id: String,
+
+    #[arg(long, help = "Application information, it's cleared if not set")]
+    info: Option<String>,
+}
+
+#[derive(Args)]
+struct DeleteAppCommand {
+    #[arg(long, help = "Application unique ID")]
+    app_id: String,
+
+    #[arg(long, help = "Delete the tasks and proofs of the application")]
+    delete_proofs: bool,
+}
+
+#[derive(Args)]
+struct GetVerificationParamsCommand {
+    #[arg(long, help = "Application unique ID")]
+    app_id: String,
+
+    #[arg(long, help = "File path to save the Groth16 verifier contract")]
+    contract: Option<PathBuf>,
+}
+
+#[derive(Args)]
+struct VerifyProofCommand {
+    #[arg(long, help = "Application unique ID")]
+    app_id: String,
+
+    #[arg(long, help = "Raw Groth16 proof file path")]
+    proof: PathBuf,
+
+    #[arg(long, help = "Public values stream file path")]
+    pv_stream: PathBuf,
+}
+
 #[derive(Args)]
 struct EstimateCostCommand {
     #[arg(long, help = "Application unique ID")]
@@ -78,6 +172,32 @@ struct ProveTaskCommand {
 
     #[arg(long, help = "Use GPU for proving (default: false, use CPU)")]
     use_gpu: bool,
+
+    #[arg(long, help = "Prove again even if the task has been submitted")]
+    force_reprove: bool,
+
+    #[arg(
+        long,
+        help = "Scheduling priority, higher is proved first (default: 0)"
+    )]
+    priority: Option<u32>,
+
+    #[arg(
+        long,
+        help = "Unix timestamp (seconds) before which the proof must be generated"
+    )]
+    deadline: Option<u64>,
+
+    #[arg(
+        long,
+        default_value = "groth16",
+        value_parser = ["groth16", "stark", "embed", "plonk"],
+        help = "Final stage of the proof"
+    )]
+    wrap: String,
+
+    #[arg(long, help = "Persist the compress and embed proofs for downloading")]
+    keep_intermediate_proofs: bool,
 }
 
 #[derive(Args)]
@@ -87,6 +207,70 @@ struct GetProvingResultCommand {
 
     #[arg(long, help = "Proving task unique ID")]
     task_id: String,
+
+    #[arg(
+        long,
+        default_value = "raw",
+        value_parser = ["raw", "calldata", "json"],
+        help = "Proof format"
+    )]
+    proof_format: String,
+
+    #[arg(long, help = "File path to save the proof")]
+    output: Option<PathBuf>,
+
+    #[arg(long, help = "File path to save the public values stream")]
+    pv_stream_output: Option<PathBuf>,
+}
+
+#[derive(Args)]
+struct GetIntermediateProofCommand {
+    #[arg(long, help = "Application unique ID")]
+    app_id: String,
+
+    #[arg(long, help = "Proving task unique ID")]
+    task_id: String,
+
+    #[arg(
+        long,
+        default_value = "compress",
+        value_parser = ["compress", "embed"],
+        help = "Proof stage"
+    )]
+    stage: String,
+
+    #[arg(long, help = "File path to save the proof")]
+    output: Option<PathBuf>,
+
+    #[arg(long, help = "File path to save the verifying key")]
+    vk_output: Option<PathBuf>,
+}
+
+#[derive(Args)]
+struct GetTaskStatusCommand {
+    #[arg(long, help = "Application unique ID")]
+    app_id: String,
+
+    #[arg(long, help = "Proving task unique ID")]
+    task_id: String,
+}
+
+#[derive(Args)]
+struct CancelTaskCommand {
+    #[arg(long, help = "Application unique ID")]
+    app_id: String,
+
+    #[arg(long, help = "Proving task unique ID")]
+    task_id: String,
+}
+
+#[derive(Args)]
+struct WatchTaskCommand {
+    #[arg(long, help = "Application unique ID")]
+    app_id: String,
+
+    #[arg(long, help = "Proving task unique ID")]
+    task_id: String,
 }
 
 #[tokio::main]
@@ -113,7 +297,81 @@ async fn main() -> Result<()> {
             };
             let res = client.register_app(req).await?.into_inner();
 
-            info!("RegisterApp: err={:?}", res.err);
+            info!(
+                "RegisterApp: err={:?}, app_id={}, registration_id={}",
+                res.err, res.app_id, res.registration_id
+            );
+        }
+        Command::GetApp(cmd) => {
+            let req = GetAppRequest {
+                app_id: cmd.app_id.unwrap_or_default(),
+                registration_id: cmd.registration_id,
+            };
+            let res = client.get_app(req).await?.into_inner();
+
+            info!(
+                "GetApp: err={:?}, app_id={}, status={:?}, failure_reason={:?}, app={:?}, vk_size={:?}",
+                res.err,
+                res.app_id,
+                res.status(),
+                res.failure_reason,
+                res.app,
+                res.vk.as_ref().map(|vk| vk.len()),
+            );
+        }
+        Command::ListApps(cmd) => {
+            let req = ListAppsRequest {
+                offset: cmd.offset,
+                limit: cmd.limit,
+            };
+            let res = client.list_apps(req).await?.into_inner();
+
+            info!("ListApps: err={:?}, total={}", res.err, res.total);
+            for app in res.apps {
+                info!("{app:?}");
+            }
+        }
+        Command::UpdateAppInfo(cmd) => {
+            let req = UpdateAppInfoRequest {
+                app_id: cmd.app_id,
+                info: cmd.info,
+            };
+            let res = client.update_app_info(req).await?.into_inner();
+
+            info!("UpdateAppInfo: err={:?}", res.err);
+        }
+        Command::DeleteApp(cmd) => {
+            let req = DeleteAppRequest {
+                app_id: cmd.app_id,
+                delete_proofs: Some(cmd.delete_proofs),
+            };
+            let res = client.delete_app(req).await?.into_inner();
+
+            info!("DeleteApp: err={:?}", res.err);
+        }
+        Command::GetVerificationParams(cmd) => {
+            let req = GetVerificationParamsRequest { app_id: cmd.app_id };
+            let res = client.get_verification_params(req).await?.into_inner();
+
+            info!(
+                "GetVerificationParams: err={:?}, vk_digest={}, vk_root={:?}, groth16_vk_hash={}",
+                res.err, res.vk_digest, res.vk_root, res.groth16_vk_hash
+            );
+
+            if let Some(path) = cmd.contract {
+                fs::write(&path, &res.verifier_contract)?;
+                info!("Groth16 verifier contract saved to {}", path.display());
+            }
+        }
+        Command::VerifyProof(cmd) => {
+            let req = VerifyProofRequest {
+                app_id: cmd.app_id,
+                proof: fs::read(cmd.proof)?,
+                pv_stream: fs::read(cmd.pv_stream)?,
+            };
+            let res = client.verify_proof(req).await?.into_inner();
+
+            info!("VerifyProof: err={:?}, valid={}", res.err, res.valid);
         }
         Command::EstimateCost(cmd) => {
             let inputs = if let Some(file_path) = cmd.inputs {
@@ -139,25 +397,144 @@ async fn main() -> Result<()> {
             } else {
                 None
             };
+            let wrap = match cmd.wrap.as_str() {
+                "stark" => WrapType::Stark,
+                "embed" => WrapType::Embed,
+                "plonk" => WrapType::Plonk,
+                _ => WrapType::Groth16,
+            };
 
             let req = ProveTaskRequest {
                 app_id: cmd.app_id,
                 task_id: cmd.task_id,
                 inputs,
                 use_gpu: Some(cmd.use_gpu),
+                force_reprove: Some(cmd.force_reprove),
+                priority: cmd.priority,
+                deadline: cmd.deadline,
+                wrap: Some(wrap.into()),
+                keep_intermediate_proofs: Some(cmd.keep_intermediate_proofs),
             };
             let res = client.prove_task(req).await?.into_inner();
 
-            info!("ProveTask: err={:?}", res.err);
+            info!(
+                "ProveTask: err={:?}, status={:?}, proof={:?}",
+                res.err,
+                res.status(),
+                res.proof
+            );
         }
         Command::GetProvingResult(cmd) => {
+            let proof_format = match cmd.proof_format.as_str() {
+                "calldata" => ProofFormat::Calldata,
+                "json" => ProofFormat::Json,
+                _ => ProofFormat::Raw,
+            };
             let req = GetProvingResultRequest {
                 app_id: cmd.app_id,
                 task_id: cmd.task_id,
+                proof_format: Some(proof_format.into()),
             };
             let res = client.get_proving_result(req).await?.into_inner();
 
-            info!("GetProvingResult: err={:?}, proof={:?}", res.err, res.proof);
+            info!(
+                "GetProvingResult: err={:?}, proof={:?}, pv_stream={:?}, pv_digest={:?}, vk_digest={:?}, wrap={:?}",
+                res.err,
+                res.proof,
+                res.pv_stream,
+                res.pv_digest,
+                res.vk_digest,
+                res.wrap(),
+            );
+
+            if let (Some(path), Some(proof)) = (cmd.output, &res.proof) {
+                fs::write(&path, proof)?;
+                info!("proof saved to {}", path.display());
+            }
+            if let (Some(path), Some(pv_stream)) = (cmd.pv_stream_output, &res.pv_stream) {
+                fs::write(&path, pv_stream)?;
+                info!("public values stream saved to {}", path.display());
+            }
+        }
+        Command::GetIntermediateProof(cmd) => {
+            let stage = match cmd.stage.as_str() {
+                "embed" => ProofStage::Embed,
+                _ => ProofStage::Compress,
+            };
+            let req = GetIntermediateProofRequest {
+                app_id: cmd.app_id,
+                task_id: cmd.task_id,
+                stage: stage.into(),
+            };
+            let res = client.get_intermediate_proof(req).await?.into_inner();
+
+            info!(
+                "GetIntermediateProof: err={:?}, proof size={} bytes, vk size={} bytes",
+                res.err,
+                res.proof.len(),
+                res.vk.len(),
+            );
+
+            if let Some(path) = cmd.output {
+                fs::write(&path, &res.proof)?;
+                info!("proof saved to {}", path.display());
+            }
+            if let Some(path) = cmd.vk_output {
+                fs::write(&path, &res.vk)?;
+                info!("verifying key saved to {}", path.display());
+            }
+        }
+        Command::GetTaskStatus(cmd) => {
+            let req = GetTaskStatusRequest {
+                app_id: cmd.app_id,
+                task_id: cmd.task_id,
+            };
+            let res = client.get_task_status(req).await?.into_inner();
+
+            info!(
+                "GetTaskStatus: err={:?}, status={:?}, failure_reason={:?}, progress={:?}, \
+                 created_at={}, started_at={:?}, finished_at={:?}, updated_at={}, \
+                 queue_position={:?}",
+                res.err,
+                res.status(),
+                res.failure_reason,
+                res.progress,
+                res.created_at,
+                res.started_at,
+                res.finished_at,
+                res.updated_at,
+                res.queue_position,
+            );
+        }
+        Command::CancelTask(cmd) => {
+            let req = CancelTaskRequest {
+                app_id: cmd.app_id,
+                task_id: cmd.task_id,
+            };
+            let res = client.cancel_task(req).await?.into_inner();
+
+            info!("CancelTask: err={:?}, status={:?}", res.err, res.status());
+        }
+        Command::WatchTask(cmd) => {
+            let req = WatchTaskRequest {
+                app_id: cmd.app_id,
+                task_id: cmd.task_id,
+            };
+            let mut stream = client.watch_task(req).await?.into_inner();
+
+            while let Some(res) = stream.message().await? {
+                info!(
+                    "WatchTask: err={:?}, event={:?}, status={:?}, index={:?}, chunks={:?}, \
+                     failure_reason={:?}, timestamp={}",
+                    res.err,
+                    res.event(),
+                    res.status(),
+                    res.index,
+                    res.chunk_start.zip(res.chunk_end),
+                    res.failure_reason,
+                    res.timestamp,
+                );
+            }
         }
     }
 
@@ -1,12 +1,12 @@
 use alloy_chains::Chain;
 use alloy_provider::{Provider, ProviderBuilder, RootProvider, WsConnect, network::AnyNetwork};
-use anyhow::{Result, anyhow};
+use anyhow::{Result, anyhow, bail};
 use clap::Parser;
 use dotenvy::dotenv;
 use futures::{StreamExt, future::ready};
 use pico_proving_service::{
-    EstimateCostRequest, ProveTaskRequest, RegisterAppRequest, app_manager::App,
-    prover_network_client::ProverNetworkClient,
+    AppStatus, EstimateCostRequest, GetAppRequest, ProveTaskRequest, RegisterAppRequest,
+    app_manager::App, prover_network_client::ProverNetworkClient,
 };
 use pico_vm::{
     configs::stark_config::KoalaBearPoseidon2 as SC, emulator::stdin::EmulatorStdinBuilder,
@@ -18,13 +18,16 @@ use rsp_host_executor::{
     create_eth_block_execution_strategy_factory,
 };
 use rsp_provider::create_provider;
-use std::{fs, path::PathBuf};
+use std::{fs, path::PathBuf, time::Duration};
+use tokio::time::sleep;
 use tonic::{codec::CompressionEncoding, transport::Channel};
 use tracing::{info, warn};
 use url::Url;
 
 // reth elf file path
 const RETH_ELF_PATH: &str = "fixtures/reth-elf";
+// interval of checking the registration status
+const APP_STATUS_INTERVAL: Duration = Duration::from_secs(5);
 
 #[derive(Parser)]
 struct Cli {
@@ -149,6 +152,9 @@ async fn main() -> Result<()> {
                 task_id: format!("task-block-{block_number}"),
                 inputs: Some(block_inputs.clone()),
                 use_gpu: Some(cli.use_gpu),
+                force_reprove: None,
+                priority: None,
+                deadline: None,
             },
         )
         .await?;
@@ -207,13 +213,30 @@ async fn register_reth(prover_network_client: &mut ProverNetworkClient<Channel>)
     let elf = fs::read(RETH_ELF_PATH)?;
 
     // generate app id
-    let app = App::new(&elf, None);
+    let app = App::new(&elf, None)?;
 
     // register reth app to service
     let req = RegisterAppRequest { elf, info: None };
-    if let Err(e) = prover_network_client.register_app(req).await {
+    let res = prover_network_client.register_app(req).await?.into_inner();
+    if res.err.is_some() {
         // ouput and ignore the error since it may have always been registered
-        warn!("RegisterApp: err={e:?}");
+        warn!("RegisterApp: err={:?}", res.err);
+    }
+    let registration_id = Some(res.registration_id).filter(|id| !id.is_empty());
+
+    // wait for the keys to be set up and saved by service
+    loop {
+        let req = GetAppRequest {
+            app_id: app.app_id.clone(),
+            registration_id: registration_id.clone(),
+        };
+        let res = prover_network_client.get_app(req).await?.into_inner();
+        match res.status() {
+            AppStatus::Ready => break,
+            AppStatus::Failed => bail!("failed to register app: {:?}", res.failure_reason),
+            status => info!("waiting for app registration, status: {status:?}"),
+        }
+        sleep(APP_STATUS_INTERVAL).await;
     }
 
     Ok(app)
@@ -246,7 +269,7 @@ async fn prove_task(
         .await?
         .into_inner();
 
-    info!("ProveTask: err={:?}", res.err);
+    info!("ProveTask: err={:?}, status={:?}", res.err, res.status());
 
     Ok(())
 }
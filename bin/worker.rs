@@ -0,0 +1,31 @@
+use anyhow::{Result, anyhow};
+use clap::Parser;
+use dotenvy::dotenv;
+use pico_proving_service::{config::WorkerConfig, proving::worker::client::RemoteWorker};
+use pico_vm::{
+    iter::{ThreadPoolBuilder, current_num_threads},
+    machine::logger::setup_logger,
+};
+use tracing::info;
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    dotenv().ok();
+    setup_logger();
+
+    ThreadPoolBuilder::new()
+        .num_threads(num_cpus::get())
+        .build_global()
+        .expect("failed to build global Rayon thread pool");
+    info!("initialized Rayon with {} threads", current_num_threads());
+
+    let cfg = WorkerConfig::parse();
+    cfg.validate().map_err(|e| anyhow!(e))?;
+    info!("starting worker with config: {:?}", cfg);
+
+    let worker = RemoteWorker::connect(cfg).await?;
+    worker.run().await?;
+
+    info!("worker exits");
+    Ok(())
+}
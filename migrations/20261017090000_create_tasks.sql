@@ -0,0 +1,14 @@
+CREATE TABLE tasks (
+    app_id TEXT NOT NULL,
+    task_id TEXT NOT NULL,
+    status TEXT NOT NULL,
+    error TEXT,
+    chunks_emulated INTEGER NOT NULL DEFAULT 0,
+    chunks_proved INTEGER NOT NULL DEFAULT 0,
+    combine_proofs INTEGER NOT NULL DEFAULT 0,
+    created_at INTEGER NOT NULL DEFAULT (strftime('%s', 'now')),
+    started_at INTEGER,
+    finished_at INTEGER,
+    updated_at INTEGER NOT NULL DEFAULT (strftime('%s', 'now')),
+    PRIMARY KEY (app_id, task_id)
+);
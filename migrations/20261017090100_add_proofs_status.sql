@@ -0,0 +1,2 @@
+ALTER TABLE proofs ADD COLUMN status TEXT NOT NULL DEFAULT 'TASK_STATUS_SUCCEEDED';
+ALTER TABLE proofs ADD COLUMN error TEXT;
@@ -0,0 +1,2 @@
+ALTER TABLE tasks ADD COLUMN inputs BLOB;
+ALTER TABLE tasks ADD COLUMN use_gpu BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1,3 @@
+ALTER TABLE proofs ADD COLUMN pv_stream BLOB;
+ALTER TABLE proofs ADD COLUMN pv_digest BLOB;
+ALTER TABLE proofs ADD COLUMN vk_digest TEXT;
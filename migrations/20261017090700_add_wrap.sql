@@ -0,0 +1,2 @@
+ALTER TABLE tasks ADD COLUMN wrap TEXT NOT NULL DEFAULT 'WRAP_TYPE_GROTH16';
+ALTER TABLE proofs ADD COLUMN wrap TEXT;
@@ -0,0 +1,11 @@
+CREATE TABLE intermediate_proofs (
+    app_id TEXT NOT NULL,
+    task_id TEXT NOT NULL,
+    stage TEXT NOT NULL,
+    proof BLOB NOT NULL,
+    vk BLOB NOT NULL,
+    created_at INTEGER NOT NULL DEFAULT (strftime('%s', 'now')),
+    PRIMARY KEY (app_id, task_id, stage)
+);
+
+ALTER TABLE tasks ADD COLUMN keep_intermediate_proofs BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1 @@
+CREATE INDEX idx_apps_elf_hash ON apps (elf_hash);
@@ -0,0 +1,21 @@
+-- the proofs could be retained after their app is deleted, so they no longer reference apps, the
+-- table is rebuilt since SQLite cannot drop a foreign key
+CREATE TABLE proofs_new (
+    app_id TEXT NOT NULL,
+    task_id TEXT NOT NULL,
+    proof BLOB,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    status TEXT NOT NULL DEFAULT 'TASK_STATUS_SUCCEEDED',
+    error TEXT,
+    pv_stream BLOB,
+    pv_digest BLOB,
+    vk_digest TEXT,
+    wrap TEXT,
+    PRIMARY KEY (app_id, task_id)
+);
+INSERT INTO proofs_new
+    (app_id, task_id, proof, created_at, status, error, pv_stream, pv_digest, vk_digest, wrap)
+SELECT app_id, task_id, proof, created_at, status, error, pv_stream, pv_digest, vk_digest, wrap
+FROM proofs;
+DROP TABLE proofs;
+ALTER TABLE proofs_new RENAME TO proofs;
@@ -1,6 +1,11 @@
-use crate::types::{DbPool, SC, Val};
-use anyhow::{Result, bail};
-use derive_more::Constructor;
+use crate::{
+    AppInfo, AppStatus, TaskStatus,
+    error::PicoError,
+    types::{DbPool, SC, Val},
+    utils::panic::panic_message,
+};
+use anyhow::{Result, anyhow};
+use dashmap::{DashMap, mapref::entry::Entry};
 use pico_vm::{
     compiler::riscv::{
         compiler::{Compiler, SourceType},
@@ -17,58 +22,174 @@ use pico_vm::{
     },
     primitives::consts::RISCV_NUM_PVS,
 };
+use sha2::{Digest, Sha256};
 use sqlx::FromRow;
-use std::sync::Arc;
-use tracing::info;
+use std::{
+    collections::HashMap,
+    panic::{AssertUnwindSafe, catch_unwind},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+use tracing::{error, info};
 
+// a failed registration is kept for polling in this duration, it's evicted once another
+// registration fails after that
+const FAILED_REGISTRATION_TTL: Duration = Duration::from_secs(3600);
+
+/// Remove the `0x` prefix of an app ID, the apps and tasks are saved without it.
+pub fn normalize_app_id(app_id: &str) -> &str {
+    app_id.strip_prefix("0x").unwrap_or(app_id)
+}
+
+/// Compiled program and keys of an application, they're shared by the proving tasks.
 #[derive(Clone)]
 pub struct App {
     pub app_id: String,
     pub program: Arc<Program>,
-    pub pk: BaseProvingKey<SC>,
-    pub vk: BaseVerifyingKey<SC>,
+    pub pk: Arc<BaseProvingKey<SC>>,
+    pub vk: Arc<BaseVerifyingKey<SC>>,
     pub info: Option<String>,
 }
 
 impl App {
-    // create an app
-    pub fn new(elf: &[u8], info: Option<String>) -> Self {
+    // create an app, the malformed or unsupported ELF is rejected with `PicoError::InvalidElf`
+    pub fn new(elf: &[u8], info: Option<String>) -> Result<Self, PicoError> {
+        validate_elf(elf)?;
+
         info!("compiling elf to program");
-        let mut program = Compiler::new(SourceType::RISCV, elf).compile();
+        let mut program = catch_unwind(|| Compiler::new(SourceType::RISCV, elf).compile())
+            .map_err(|e| {
+                PicoError::InvalidElf(format!("failed to compile: {}", panic_message(e)))
+            })?;
+        if program.instructions.is_empty() {
+            return Err(PicoError::InvalidElf("no instructions".to_string()));
+        }
 
         if vk_verification_enabled() {
             info!("padding shape");
             let shape_config = RiscvShapeConfig::<Val>::default();
-            let p = Arc::get_mut(&mut program).expect("cannot get program");
-            shape_config
-                .padding_preprocessed_shape(p)
-                .expect("cannot padding preprocessed shape");
+            let p = Arc::get_mut(&mut program)
+                .ok_or_else(|| PicoError::InternalError("cannot get program".to_string()))?;
+            shape_config.padding_preprocessed_shape(p).map_err(|e| {
+                PicoError::InvalidElf(format!(
+                    "program size is not supported by the preprocessed shapes: {e:?}"
+                ))
+            })?;
         }
 
         info!("creating riscv machine");
         let machine = RiscvMachine::new(SC::default(), RiscvChipType::all_chips(), RISCV_NUM_PVS);
 
         info!("setting up pk and vk");
-        let (pk, vk) = machine.setup_keys(&program);
+        let (pk, vk) =
+            catch_unwind(AssertUnwindSafe(|| machine.setup_keys(&program))).map_err(|e| {
+                PicoError::InternalError(format!("failed to setup keys: {}", panic_message(e)))
+            })?;
 
+        // app-id must be an uint256 starting with 0x
         let app_id = vk.hash_str_via_bn254();
-        assert_eq!(
-            app_id.len(),
-            66,
-            "app-id must be an uint256 starting with 0x",
-        );
-        let app_id = app_id[2..].to_string();
+        let app_id = app_id
+            .strip_prefix("0x")
+            .filter(|id| id.len() == 64)
+            .ok_or_else(|| PicoError::InternalError(format!("invalid app id {app_id}")))?
+            .to_string();
 
-        Self {
+        Ok(Self {
             app_id,
             program,
-            pk,
-            vk,
+            pk: Arc::new(pk),
+            vk: Arc::new(vk),
             info,
-        }
+        })
     }
 }
 
+// ELF32 header fields, the program is compiled for the little-endian RISC-V 32 executable
+const ELF_MAGIC: &[u8] = b"\x7fELF";
+const ELF32_HEADER_SIZE: usize = 52;
+const ELF32_PROGRAM_HEADER_SIZE: usize = 32;
+const ELFCLASS32: u8 = 1;
+const ELFDATA2LSB: u8 = 1;
+const ET_EXEC: u16 = 2;
+const EM_RISCV: u16 = 0xf3;
+const PT_LOAD: u32 = 1;
+const PF_X: u32 = 1;
+
+// check the ELF header before compiling, the compiler panics on the malformed ELF
+fn validate_elf(elf: &[u8]) -> Result<(), PicoError> {
+    let invalid = |msg: &str| PicoError::InvalidElf(msg.to_string());
+    let read_u16 = |offset: usize| u16::from_le_bytes([elf[offset], elf[offset + 1]]);
+    let read_u32 = |offset: usize| {
+        u32::from_le_bytes([
+            elf[offset],
+            elf[offset + 1],
+            elf[offset + 2],
+            elf[offset + 3],
+        ])
+    };
+
+    if elf.len() < ELF32_HEADER_SIZE {
+        return Err(invalid("file is too small for an ELF header"));
+    }
+    if !elf.starts_with(ELF_MAGIC) {
+        return Err(invalid("bad magic number"));
+    }
+    if elf[4] != ELFCLASS32 {
+        return Err(invalid("not a 32-bit ELF"));
+    }
+    if elf[5] != ELFDATA2LSB {
+        return Err(invalid("not a little-endian ELF"));
+    }
+    if read_u16(16) != ET_EXEC {
+        return Err(invalid("not an executable ELF"));
+    }
+    if read_u16(18) != EM_RISCV {
+        return Err(invalid("not a RISC-V ELF"));
+    }
+
+    let entry = read_u32(24);
+    if entry == 0 || entry % 4 != 0 {
+        return Err(PicoError::InvalidElf(format!(
+            "invalid entry point {entry:#x}"
+        )));
+    }
+
+    // the entry point must be in an executable loadable segment
+    let ph_offset = read_u32(28) as usize;
+    let ph_entry_size = read_u16(42) as usize;
+    let ph_count = read_u16(44) as usize;
+    if ph_count == 0 {
+        return Err(invalid("no program headers"));
+    }
+    if ph_entry_size < ELF32_PROGRAM_HEADER_SIZE
+        || ph_offset
+            .checked_add(ph_entry_size * ph_count)
+            .is_none_or(|end| end > elf.len())
+    {
+        return Err(invalid("program headers are out of bounds"));
+    }
+    let in_code = (0..ph_count).any(|i| {
+        let offset = ph_offset + i * ph_entry_size;
+        let (p_type, p_vaddr, p_memsz, p_flags) = (
+            read_u32(offset),
+            read_u32(offset + 8),
+            read_u32(offset + 20),
+            read_u32(offset + 24),
+        );
+        p_type == PT_LOAD
+            && p_flags & PF_X != 0
+            && entry >= p_vaddr
+            && (entry as u64) < p_vaddr as u64 + p_memsz as u64
+    });
+    if !in_code {
+        return Err(PicoError::InvalidElf(format!(
+            "entry point {entry:#x} is not in an executable segment"
+        )));
+    }
+
+    Ok(())
+}
+
 #[derive(Debug, FromRow)]
 pub struct AppRow {
     pub app_id: String,
@@ -78,51 +199,253 @@ pub struct AppRow {
     pub info: Option<String>,
 }
 
-impl From<App> for AppRow {
-    fn from(app: App) -> Self {
+impl TryFrom<App> for AppRow {
+    type Error = anyhow::Error;
+
+    fn try_from(app: App) -> Result<Self> {
         let app_id = app.app_id;
-        let program = bincode::serialize(&app.program).unwrap();
-        let pk = bincode::serialize(&app.pk).unwrap();
-        let vk = bincode::serialize(&app.vk).unwrap();
+        let program = bincode::serialize(&app.program)?;
+        let pk = bincode::serialize(&app.pk)?;
+        let vk = bincode::serialize(&app.vk)?;
         let info = app.info;
 
-        Self {
+        Ok(Self {
             app_id,
             program,
             pk,
             vk,
             info,
-        }
+        })
     }
 }
 
-impl From<AppRow> for App {
-    fn from(row: AppRow) -> Self {
+impl TryFrom<AppRow> for App {
+    type Error = anyhow::Error;
+
+    fn try_from(row: AppRow) -> Result<Self> {
         let app_id = row.app_id;
-        let program = Arc::new(bincode::deserialize(&row.program).unwrap());
-        let pk = bincode::deserialize(&row.pk).unwrap();
-        let vk = bincode::deserialize(&row.vk).unwrap();
+        let program = Arc::new(
+            bincode::deserialize(&row.program)
+                .map_err(|e| anyhow!("failed to deserialize program of app {app_id}: {e}"))?,
+        );
+        let pk = Arc::new(
+            bincode::deserialize(&row.pk)
+                .map_err(|e| anyhow!("failed to deserialize pk of app {app_id}: {e}"))?,
+        );
+        let vk = Arc::new(
+            bincode::deserialize(&row.vk)
+                .map_err(|e| anyhow!("failed to deserialize vk of app {app_id}: {e}"))?,
+        );
         let info = row.info;
 
-        Self {
+        Ok(Self {
             app_id,
             program,
             pk,
             vk,
             info,
+        })
+    }
+}
+
+/// Metadata of a registered app without its program and keys.
+#[derive(Debug, FromRow)]
+pub struct AppMetaRow {
+    pub app_id: String,
+    pub info: Option<String>,
+    pub created_at: Option<i64>,
+    pub elf_hash: Option<String>,
+    pub program_size: i64,
+}
+
+impl From<AppMetaRow> for AppInfo {
+    fn from(row: AppMetaRow) -> Self {
+        Self {
+            app_id: row.app_id,
+            info: row.info,
+            created_at: row.created_at.map(|t| t as u64),
+            elf_hash: row.elf_hash,
+            program_size: row.program_size as u64,
+        }
+    }
+}
+
+/// Result of deleting an app.
+#[derive(Debug)]
+pub enum AppDeletion {
+    Deleted,
+    NotFound,
+    // the program and keys of a registering app are being saved
+    Registering,
+    // number of the queued or in-progress tasks of the app
+    PendingTasks(u64),
+}
+
+impl AppRow {
+    // serialized size of the program and keys, it's used to measure the memory of a cached app
+    pub fn size(&self) -> usize {
+        self.program.len() + self.pk.len() + self.vk.len()
+    }
+}
+
+struct CachedApp {
+    app: App,
+    size: usize,
+    // logical time of the last access
+    last_used: u64,
+}
+
+#[derive(Default)]
+struct AppCacheInner {
+    apps: HashMap<String, CachedApp>,
+    // total size of the cached apps
+    size: usize,
+    clock: u64,
+}
+
+/// LRU cache of the deserialized apps bounded by their serialized size, the least recently used
+/// apps are evicted once the capacity is exceeded.
+pub struct AppCache {
+    capacity: usize,
+    inner: Mutex<AppCacheInner>,
+}
+
+impl AppCache {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            inner: Mutex::default(),
         }
     }
+
+    pub fn get(&self, app_id: &str) -> Option<App> {
+        let inner = &mut *self.inner.lock().unwrap();
+        inner.clock += 1;
+
+        let cached = inner.apps.get_mut(app_id)?;
+        cached.last_used = inner.clock;
+
+        Some(cached.app.clone())
+    }
+
+    pub fn remove(&self, app_id: &str) {
+        let inner = &mut *self.inner.lock().unwrap();
+        if let Some(removed) = inner.apps.remove(app_id) {
+            inner.size -= removed.size;
+        }
+    }
+
+    pub fn insert(&self, app: App, size: usize) {
+        // the app larger than the whole cache is never cached
+        if size > self.capacity {
+            return;
+        }
+
+        let inner = &mut *self.inner.lock().unwrap();
+        inner.clock += 1;
+
+        let app_id = app.app_id.clone();
+        let cached = CachedApp {
+            app,
+            size,
+            last_used: inner.clock,
+        };
+        if let Some(old) = inner.apps.insert(app_id, cached) {
+            inner.size -= old.size;
+        }
+        inner.size += size;
+
+        while inner.size > self.capacity {
+            let Some(lru_app_id) = inner
+                .apps
+                .iter()
+                .min_by_key(|(_, cached)| cached.last_used)
+                .map(|(app_id, _)| app_id.clone())
+            else {
+                break;
+            };
+            if let Some(evicted) = inner.apps.remove(&lru_app_id) {
+                info!(
+                    "[app-cache] evict app {lru_app_id} of {} bytes",
+                    evicted.size
+                );
+                inner.size -= evicted.size;
+            }
+        }
+    }
+}
+
+// registration of an app which is not saved to DB yet
+#[derive(Clone, Debug)]
+enum Registration {
+    // the app ID is set once the keys are set up
+    Registering(Option<String>),
+    // failure reason and the time it failed
+    Failed(String, Instant),
 }
 
-#[derive(Constructor)]
+/// Result of registering an ELF, it's identified by the registration ID (sha256 hex of the ELF).
+#[derive(Debug)]
+pub enum AppRegistration {
+    // the keys are being set up in background
+    Registering {
+        registration_id: String,
+    },
+    // the ELF has been registered and the app is ready
+    Registered {
+        registration_id: String,
+        app_id: String,
+    },
+}
+
+/// Status of a registration looked up by its registration ID.
+#[derive(Debug)]
+pub struct RegistrationStatus {
+    pub status: AppStatus,
+    pub failure_reason: Option<String>,
+    // set once the keys are set up
+    pub app_id: Option<String>,
+}
+
+#[derive(Clone)]
 pub struct AppManager {
     db_pool: Arc<DbPool>,
+    cache: Arc<AppCache>,
+    // apps in registration by registration ID (sha256 hex of the ELF), an app is ready once it's
+    // saved to DB
+    registrations: Arc<DashMap<String, Registration>>,
 }
 
 impl AppManager {
+    pub fn new(db_pool: Arc<DbPool>, cache: Arc<AppCache>) -> Self {
+        Self {
+            db_pool,
+            cache,
+            registrations: Arc::new(DashMap::new()),
+        }
+    }
+
     pub async fn get_app(&self, app_id: &str) -> Result<Option<App>> {
-        // remove the prefix `0x`
-        let app_id = app_id.strip_prefix("0x").unwrap_or(app_id);
+        let app_id = normalize_app_id(app_id);
+        if let Some(app) = self.cache.get(app_id) {
+            return Ok(Some(app));
+        }
+
+        let Some(row) = self.get_app_row(app_id).await? else {
+            return Ok(None);
+        };
+
+        // deserializing the keys of a big app takes seconds
+        let size = row.size();
+        let app = tokio::task::spawn_blocking(move || App::try_from(row)).await??;
+        self.cache.insert(app.clone(), size);
+
+        Ok(Some(app))
+    }
+
+    // get the serialized app, it's sent to the remote workers directly
+    pub async fn get_app_row(&self, app_id: &str) -> Result<Option<AppRow>> {
+        let app_id = normalize_app_id(app_id);
 
         let row = sqlx::query_as::<_, AppRow>(
             "SELECT app_id, program, pk, vk, info FROM apps WHERE app_id = ?",
@@ -131,31 +454,460 @@ impl AppManager {
         .fetch_optional(&*self.db_pool)
         .await?;
 
-        Ok(row.map(Into::into))
+        Ok(row)
     }
 
-    pub async fn set_app(&self, elf: &[u8], info: Option<String>) -> Result<App> {
-        let app = App::new(elf, info);
+    // get the status of an app by app ID, the failed registrations are only found by their
+    // registration IDs since the app ID is unknown if the keys are not set up
+    pub async fn get_app_status(&self, app_id: &str) -> Result<AppStatus> {
+        let app_id = normalize_app_id(app_id);
 
-        let app_id = &app.app_id;
-        info!("register an new app {app_id}");
+        if self.is_registering(app_id) {
+            return Ok(AppStatus::Registering);
+        }
+        let status = if self.app_exists(app_id).await? {
+            AppStatus::Ready
+        } else {
+            AppStatus::Unknown
+        };
+
+        Ok(status)
+    }
 
-        if self.get_app(app_id).await?.is_some() {
-            bail!("app already exists {app_id}");
+    // get the status of a registration by the registration ID returned by `register_app`
+    pub async fn get_registration(&self, registration_id: &str) -> Result<RegistrationStatus> {
+        if let Some(registration) = self.registrations.get(registration_id) {
+            return Ok(match registration.value() {
+                Registration::Registering(app_id) => RegistrationStatus {
+                    status: AppStatus::Registering,
+                    failure_reason: None,
+                    app_id: app_id.clone(),
+                },
+                Registration::Failed(reason, _) => RegistrationStatus {
+                    status: AppStatus::Failed,
+                    failure_reason: Some(reason.clone()),
+                    app_id: None,
+                },
+            });
         }
 
-        let row = AppRow::from(app.clone());
+        let app_id = self.find_app_by_elf_hash(registration_id).await?;
+        let status = if app_id.is_some() {
+            AppStatus::Ready
+        } else {
+            AppStatus::Unknown
+        };
+
+        Ok(RegistrationStatus {
+            status,
+            failure_reason: None,
+            app_id,
+        })
+    }
+
+    // whether the program and keys of the app are being saved
+    fn is_registering(&self, app_id: &str) -> bool {
+        self.registrations.iter().any(|registration| {
+            matches!(registration.value(), Registration::Registering(Some(id)) if id == app_id)
+        })
+    }
+
+    async fn find_app_by_elf_hash(&self, elf_hash: &str) -> Result<Option<String>> {
+        let row = sqlx::query_as::<_, (String,)>("SELECT app_id FROM apps WHERE elf_hash = ?")
+            .bind(elf_hash)
+            .fetch_optional(&*self.db_pool)
+            .await?;
+
+        Ok(row.map(|(app_id,)| app_id))
+    }
+
+    async fn app_exists(&self, app_id: &str) -> Result<bool> {
+        if self.cache.get(app_id).is_some() {
+            return Ok(true);
+        }
+
+        let row = sqlx::query("SELECT 1 FROM apps WHERE app_id = ?")
+            .bind(app_id)
+            .fetch_optional(&*self.db_pool)
+            .await?;
+
+        Ok(row.is_some())
+    }
+
+    /// Register an app in background, it returns the registration ID (sha256 hex of the ELF) once
+    /// the ELF header is checked. The app ID is the digest of riscv vk which is only known after
+    /// the keys are set up, so the registration status and app ID are looked up by the
+    /// registration ID. The app is ready for proving after it's saved to DB. The app ID is
+    /// returned directly if the ELF has been registered.
+    pub async fn register_app(
+        &self,
+        elf: Vec<u8>,
+        info: Option<String>,
+    ) -> Result<AppRegistration, PicoError> {
+        validate_elf(&elf)?;
+        let registration_id = hex::encode(Sha256::digest(&elf));
+
+        // reject the duplicate ELF before the key setup, a failed registration could be retried
+        match self.registrations.entry(registration_id.clone()) {
+            Entry::Occupied(mut entry) => match entry.get() {
+                Registration::Registering(_) => {
+                    return Err(PicoError::AlreadyExists(format!(
+                        "elf {registration_id} is registering"
+                    )));
+                }
+                Registration::Failed(..) => {
+                    entry.insert(Registration::Registering(None));
+                }
+            },
+            Entry::Vacant(entry) => {
+                entry.insert(Registration::Registering(None));
+            }
+        }
+        match self.find_app_by_elf_hash(&registration_id).await {
+            Ok(None) => (),
+            Ok(Some(app_id)) => {
+                self.registrations.remove(&registration_id);
+                return Ok(AppRegistration::Registered {
+                    registration_id,
+                    app_id,
+                });
+            }
+            Err(e) => {
+                self.registrations.remove(&registration_id);
+                return Err(PicoError::InternalError(e.to_string()));
+            }
+        }
+        info!("register an new app of elf {registration_id}");
+
+        let manager = self.clone();
+        let elf_hash = registration_id.clone();
+        tokio::spawn(async move {
+            if let Err(e) = manager.setup_app(elf, info, elf_hash.clone()).await {
+                error!("failed to register app of elf {elf_hash}: {e}");
+                manager.fail_registration(elf_hash, e.to_string());
+            }
+        });
+
+        Ok(AppRegistration::Registering { registration_id })
+    }
+
+    // record a failed registration, and evict the expired failures so they're bounded by the TTL
+    fn fail_registration(&self, registration_id: String, reason: String) {
+        let now = Instant::now();
+        remove_expired_failures(&self.registrations, now);
+        self.registrations
+            .insert(registration_id, Registration::Failed(reason, now));
+    }
+
+    // set up the keys and save the app, the registration is removed once it's saved
+    async fn setup_app(&self, elf: Vec<u8>, info: Option<String>, elf_hash: String) -> Result<()> {
+        // the key setup of a big ELF takes a long time
+        let app = tokio::task::spawn_blocking(move || App::new(&elf, info)).await??;
+        let app_id = app.app_id.clone();
+
+        // the same program could be built from a different ELF, or registered before the ELF
+        // hash is recorded
+        if self.app_exists(&app_id).await? {
+            return Err(anyhow!("app already exists {app_id}"));
+        }
+        self.registrations.insert(
+            elf_hash.clone(),
+            Registration::Registering(Some(app_id.clone())),
+        );
+
+        self.save_app(app, elf_hash.clone()).await?;
+        info!("app {app_id} is ready");
+        self.registrations.remove(&elf_hash);
+
+        Ok(())
+    }
+
+    async fn save_app(&self, app: App, elf_hash: String) -> Result<()> {
+        // serializing the keys of a big app takes seconds
+        let row = {
+            let app = app.clone();
+            tokio::task::spawn_blocking(move || AppRow::try_from(app)).await??
+        };
 
         info!("saving app to DB");
-        sqlx::query("INSERT INTO apps (app_id, program, pk, vk, info) VALUES (?, ?, ?, ?, ?)")
-            .bind(&row.app_id)
-            .bind(&row.program)
-            .bind(&row.pk)
-            .bind(&row.vk)
-            .bind(&row.info)
+        sqlx::query(
+            "INSERT INTO apps (app_id, program, pk, vk, info, elf_hash, created_at) \
+             VALUES (?, ?, ?, ?, ?, ?, strftime('%s', 'now'))",
+        )
+        .bind(&row.app_id)
+        .bind(&row.program)
+        .bind(&row.pk)
+        .bind(&row.vk)
+        .bind(&row.info)
+        .bind(&elf_hash)
+        .execute(&*self.db_pool)
+        .await?;
+        self.cache.insert(app, row.size());
+
+        Ok(())
+    }
+
+    pub async fn get_app_meta(&self, app_id: &str) -> Result<Option<AppMetaRow>> {
+        let app_id = normalize_app_id(app_id);
+
+        let row = sqlx::query_as::<_, AppMetaRow>(
+            "SELECT app_id, info, created_at, elf_hash, length(program) AS program_size \
+             FROM apps WHERE app_id = ?",
+        )
+        .bind(app_id)
+        .fetch_optional(&*self.db_pool)
+        .await?;
+
+        Ok(row)
+    }
+
+    // get the serialized riscv vk of an app
+    pub async fn get_app_vk(&self, app_id: &str) -> Result<Option<Vec<u8>>> {
+        let app_id = normalize_app_id(app_id);
+
+        let vk = sqlx::query_as::<_, (Vec<u8>,)>("SELECT vk FROM apps WHERE app_id = ?")
+            .bind(app_id)
+            .fetch_optional(&*self.db_pool)
+            .await?
+            .map(|row| row.0);
+
+        Ok(vk)
+    }
+
+    // list the registered apps with the total count, the latest registered first
+    pub async fn list_apps(&self, offset: u64, limit: u64) -> Result<(Vec<AppMetaRow>, u64)> {
+        let rows = sqlx::query_as::<_, AppMetaRow>(
+            "SELECT app_id, info, created_at, elf_hash, length(program) AS program_size \
+             FROM apps ORDER BY created_at DESC, app_id LIMIT ? OFFSET ?",
+        )
+        .bind(limit as i64)
+        .bind(offset as i64)
+        .fetch_all(&*self.db_pool)
+        .await?;
+        let (total,) = sqlx::query_as::<_, (i64,)>("SELECT COUNT(*) FROM apps")
+            .fetch_one(&*self.db_pool)
+            .await?;
+
+        Ok((rows, total as u64))
+    }
+
+    // update the program information of an app, return false if the app is not found
+    pub async fn update_app_info(&self, app_id: &str, info: Option<String>) -> Result<bool> {
+        let app_id = normalize_app_id(app_id);
+
+        let res = sqlx::query("UPDATE apps SET info = ? WHERE app_id = ?")
+            .bind(&info)
+            .bind(app_id)
             .execute(&*self.db_pool)
             .await?;
+        // the cached app is loaded again with the new information
+        self.cache.remove(app_id);
+
+        Ok(res.rows_affected() > 0)
+    }
+
+    /// Delete an app if it has no queued or in-progress tasks. The tasks and proofs of the app are
+    /// deleted as well if `delete_proofs` is set, otherwise they're retained for fetching the
+    /// proving results.
+    pub async fn delete_app(&self, app_id: &str, delete_proofs: bool) -> Result<AppDeletion> {
+        let app_id = normalize_app_id(app_id);
+
+        if self.is_registering(app_id) {
+            return Ok(AppDeletion::Registering);
+        }
+
+        // the app is deleted before checking its tasks, so no task could be added in between
+        let mut tx = self.db_pool.begin().await?;
+        let res = sqlx::query("DELETE FROM apps WHERE app_id = ?")
+            .bind(app_id)
+            .execute(&mut *tx)
+            .await?;
+        if res.rows_affected() == 0 {
+            return Ok(AppDeletion::NotFound);
+        }
+
+        let (pending,) = sqlx::query_as::<_, (i64,)>(
+            "SELECT COUNT(*) FROM tasks WHERE app_id = ? AND status NOT IN (?, ?, ?)",
+        )
+        .bind(app_id)
+        .bind(TaskStatus::Succeeded.as_str_name())
+        .bind(TaskStatus::Failed.as_str_name())
+        .bind(TaskStatus::Cancelled.as_str_name())
+        .fetch_one(&mut *tx)
+        .await?;
+        if pending > 0 {
+            return Ok(AppDeletion::PendingTasks(pending as u64));
+        }
+
+        if delete_proofs {
+            sqlx::query("DELETE FROM proofs WHERE app_id = ?")
+                .bind(app_id)
+                .execute(&mut *tx)
+                .await?;
+            sqlx::query("DELETE FROM intermediate_proofs WHERE app_id = ?")
+                .bind(app_id)
+                .execute(&mut *tx)
+                .await?;
+            sqlx::query("DELETE FROM tasks WHERE app_id = ?")
+                .bind(app_id)
+                .execute(&mut *tx)
+                .await?;
+        }
+        tx.commit().await?;
+        self.cache.remove(app_id);
+        info!("app {app_id} deleted, delete_proofs: {delete_proofs}");
+
+        Ok(AppDeletion::Deleted)
+    }
+}
+
+// remove the failed registrations older than the TTL
+fn remove_expired_failures(registrations: &DashMap<String, Registration>, now: Instant) {
+    registrations.retain(|_, registration| match registration {
+        Registration::Registering(_) => true,
+        Registration::Failed(_, failed_at) => {
+            now.saturating_duration_since(*failed_at) < FAILED_REGISTRATION_TTL
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{Rng, SeedableRng, rngs::StdRng};
+
+    const ENTRY: u32 = 0x1000;
+
+    // minimal RISC-V 32 executable with one loadable code segment of the entry point
+    fn valid_elf() -> Vec<u8> {
+        let mut elf = vec![0; ELF32_HEADER_SIZE + ELF32_PROGRAM_HEADER_SIZE];
+        elf[..4].copy_from_slice(ELF_MAGIC);
+        elf[4] = ELFCLASS32;
+        elf[5] = ELFDATA2LSB;
+        elf[16..18].copy_from_slice(&ET_EXEC.to_le_bytes());
+        elf[18..20].copy_from_slice(&EM_RISCV.to_le_bytes());
+        elf[24..28].copy_from_slice(&ENTRY.to_le_bytes());
+        elf[28..32].copy_from_slice(&(ELF32_HEADER_SIZE as u32).to_le_bytes());
+        elf[42..44].copy_from_slice(&(ELF32_PROGRAM_HEADER_SIZE as u16).to_le_bytes());
+        elf[44..46].copy_from_slice(&1u16.to_le_bytes());
+
+        let ph = ELF32_HEADER_SIZE;
+        elf[ph..ph + 4].copy_from_slice(&PT_LOAD.to_le_bytes());
+        elf[ph + 8..ph + 12].copy_from_slice(&ENTRY.to_le_bytes());
+        elf[ph + 20..ph + 24].copy_from_slice(&0x100u32.to_le_bytes());
+        elf[ph + 24..ph + 28].copy_from_slice(&PF_X.to_le_bytes());
+
+        elf
+    }
+
+    fn assert_invalid(elf: &[u8]) {
+        assert!(matches!(validate_elf(elf), Err(PicoError::InvalidElf(_))));
+        assert!(matches!(App::new(elf, None), Err(PicoError::InvalidElf(_))));
+    }
+
+    #[test]
+    fn test_valid_header() {
+        assert!(validate_elf(&valid_elf()).is_ok());
+    }
+
+    #[test]
+    fn test_truncated_header() {
+        let elf = valid_elf();
+        assert_invalid(&[]);
+        assert_invalid(&elf[..ELF32_HEADER_SIZE - 1]);
+    }
+
+    #[test]
+    fn test_bad_magic() {
+        let mut elf = valid_elf();
+        elf[0] = 0;
+        assert_invalid(&elf);
+    }
+
+    #[test]
+    fn test_elf_class_64() {
+        let mut elf = valid_elf();
+        elf[4] = 2;
+        assert_invalid(&elf);
+    }
+
+    #[test]
+    fn test_big_endian() {
+        let mut elf = valid_elf();
+        elf[5] = 2;
+        assert_invalid(&elf);
+    }
+
+    #[test]
+    fn test_not_riscv() {
+        // x86
+        let mut elf = valid_elf();
+        elf[18..20].copy_from_slice(&3u16.to_le_bytes());
+        assert_invalid(&elf);
+    }
+
+    #[test]
+    fn test_entry_out_of_code() {
+        // beyond the end of the code segment
+        let mut elf = valid_elf();
+        elf[24..28].copy_from_slice(&(ENTRY + 0x100).to_le_bytes());
+        assert_invalid(&elf);
+
+        // in a segment which is not executable
+        let mut elf = valid_elf();
+        let ph = ELF32_HEADER_SIZE;
+        elf[ph + 24..ph + 28].copy_from_slice(&0u32.to_le_bytes());
+        assert_invalid(&elf);
+    }
+
+    #[test]
+    fn test_program_headers_out_of_bounds() {
+        let elf = valid_elf();
+        assert_invalid(&elf[..elf.len() - 1]);
+
+        let mut elf = valid_elf();
+        elf[28..32].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert_invalid(&elf);
+
+        let mut elf = valid_elf();
+        elf[44..46].copy_from_slice(&u16::MAX.to_le_bytes());
+        assert_invalid(&elf);
+    }
+
+    #[test]
+    fn test_random_blobs() {
+        let mut rng = StdRng::seed_from_u64(0);
+        for _ in 0..1000 {
+            let mut elf = vec![0; rng.gen_range(0..256)];
+            rng.fill(&mut elf[..]);
+            assert_invalid(&elf);
+        }
+
+        // keep the valid identification to reach the program header checks, a random header
+        // could pass them, but it never panics
+        for _ in 0..1000 {
+            let mut elf = vec![0; rng.gen_range(ELF32_HEADER_SIZE..256)];
+            rng.fill(&mut elf[..]);
+            elf[..20].copy_from_slice(&valid_elf()[..20]);
+            if let Err(e) = validate_elf(&elf) {
+                assert!(matches!(e, PicoError::InvalidElf(_)));
+            }
+        }
+    }
+
+    #[test]
+    fn test_remove_expired_failures() {
+        let registrations = DashMap::new();
+        let start = Instant::now();
+        let failed = |at| Registration::Failed("failed".to_string(), at);
+        registrations.insert("registering".to_string(), Registration::Registering(None));
+        registrations.insert("expired".to_string(), failed(start));
+        registrations.insert("failed".to_string(), failed(start + Duration::from_secs(1)));
+
+        remove_expired_failures(&registrations, start + FAILED_REGISTRATION_TTL);
 
-        Ok(app)
+        let mut ids: Vec<_> = registrations.iter().map(|r| r.key().clone()).collect();
+        ids.sort();
+        assert_eq!(ids, ["failed", "registering"]);
     }
 }
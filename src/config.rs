@@ -1,9 +1,11 @@
 use crate::{
     impl_auth_config,
+    proving::gnark::GnarkConfig,
     utils::auth::{AuthConfig, AuthMethod},
 };
 use clap::Parser;
-use std::net::SocketAddr;
+use pico_perf::common::{bench_field::BenchField, gnark_utils::get_download_path};
+use std::{net::SocketAddr, path::PathBuf, time::Duration};
 
 #[derive(Debug, Parser, Clone)]
 #[clap(author, version, about, long_about = None)]
@@ -24,6 +26,29 @@ pub struct ServiceConfig {
     )]
     pub grpc_addr: SocketAddr,
 
+    #[clap(
+        long,
+        env = "WORKER_GRPC_ADDR",
+        help = "gRPC listen address for remote workers, disabled if not set"
+    )]
+    pub worker_grpc_addr: Option<SocketAddr>,
+
+    #[clap(
+        long,
+        env = "WORKER_HEARTBEAT_TIMEOUT",
+        default_value = "30",
+        help = "Seconds without heartbeats before a remote worker is considered dead"
+    )]
+    pub worker_heartbeat_timeout: u64,
+
+    #[clap(
+        long,
+        env = "TASK_LEASE_TIMEOUT",
+        default_value = "30",
+        help = "Seconds before a task pulled by a remote worker is re-dispatched if not acknowledged"
+    )]
+    pub task_lease_timeout: u64,
+
     #[clap(
         long,
         env = "AUTH_METHOD",
@@ -57,12 +82,104 @@ pub struct ServiceConfig {
     )]
     pub prover_count: usize,
 
+    #[clap(
+        long,
+        env = "RECURSION_KEYS_DIR",
+        help = "Directory to persist the compress and embed keys, they're rebuilt on restart if not set"
+    )]
+    pub recursion_keys_dir: Option<PathBuf>,
+
+    #[clap(
+        long,
+        env = "APP_CACHE_SIZE",
+        default_value = "4096",
+        help = "Maximum memory (MB) of the cached app programs and keys, 0 to disable the cache"
+    )]
+    pub app_cache_size: usize,
+
+    #[clap(
+        long,
+        env = "MAX_CONCURRENT_TASKS",
+        default_value = "1",
+        help = "Maximum proving tasks to run concurrently on the shared provers"
+    )]
+    pub max_concurrent_tasks: usize,
+
+    #[clap(
+        long,
+        env = "CHUNK_RETRY_COUNT",
+        default_value = "3",
+        help = "Maximum retries of a failed chunk proof before failing the task"
+    )]
+    pub chunk_retry_count: usize,
+
+    #[clap(
+        long,
+        env = "PROVING_TIMEOUT",
+        help = "Default proving timeout in seconds, a task fails if it's not proved in time"
+    )]
+    pub proving_timeout: Option<u64>,
+
     #[clap(
         long,
         env = "MAX_EMULATION_CYCLES",
         help = "maximum supported emulation cycles"
     )]
     pub max_emulation_cycles: Option<u64>,
+
+    #[clap(
+        long,
+        env = "GNARK_URL",
+        default_value = "http://127.0.0.1:9099",
+        help = "URL of the gnark server for the on-chain proofs"
+    )]
+    pub gnark_url: String,
+
+    #[clap(
+        long,
+        env = "GNARK_PLONK_URL",
+        help = "URL of the gnark PLONK server with its own keys, PLONK wrapping is disabled if not set"
+    )]
+    pub gnark_plonk_url: Option<String>,
+
+    #[clap(
+        long,
+        env = "GNARK_REQUEST_TIMEOUT",
+        default_value = "3600",
+        help = "Timeout of a gnark proving request in seconds"
+    )]
+    pub gnark_request_timeout: u64,
+
+    #[clap(
+        long,
+        env = "GNARK_MAX_RETRIES",
+        default_value = "3",
+        help = "Maximum retries of a gnark request if the server is unreachable or fails"
+    )]
+    pub gnark_max_retries: usize,
+
+    #[clap(
+        long,
+        env = "GNARK_RETRY_INTERVAL",
+        default_value = "5",
+        help = "Interval between the gnark request retries in seconds"
+    )]
+    pub gnark_retry_interval: u64,
+
+    #[clap(
+        long,
+        env = "GNARK_DOWNLOAD_PATH",
+        help = "Directory of the gnark vm_pk, vm_vk and vm_ccs, default to the pico download path"
+    )]
+    pub gnark_download_path: Option<String>,
+
+    #[clap(
+        long,
+        env = "GNARK_MANAGE_DOCKER",
+        default_value = "false",
+        help = "Start and monitor the gnark docker container from the service"
+    )]
+    pub gnark_manage_docker: bool,
 }
 
 impl_auth_config!(ServiceConfig);
@@ -71,4 +188,110 @@ impl ServiceConfig {
     pub fn validate(&self) -> Result<(), String> {
         self.validate_auth()
     }
+
+    pub fn gnark_config(&self) -> GnarkConfig {
+        GnarkConfig {
+            url: self.gnark_url.clone(),
+            plonk_url: self.gnark_plonk_url.clone(),
+            request_timeout: Duration::from_secs(self.gnark_request_timeout),
+            max_retries: self.gnark_max_retries,
+            retry_interval: Duration::from_secs(self.gnark_retry_interval),
+            download_path: self
+                .gnark_download_path
+                .clone()
+                .unwrap_or_else(|| get_download_path(BenchField::KoalaBear)),
+            manage_docker: self.gnark_manage_docker,
+        }
+    }
+}
+
+#[derive(Debug, Parser, Clone)]
+#[clap(author, version, about, long_about = None)]
+pub struct WorkerConfig {
+    #[clap(
+        long,
+        env = "GATEWAY_ADDR",
+        default_value = "http://[::]:50053",
+        help = "gRPC address of the gateway for remote workers"
+    )]
+    pub gateway_addr: String,
+
+    #[clap(
+        long,
+        env = "WORKER_NAME",
+        help = "Unique worker name, default to worker-<pid>"
+    )]
+    pub worker_name: Option<String>,
+
+    #[clap(
+        long,
+        env = "WORKER_IP",
+        default_value = "127.0.0.1",
+        help = "IP address of this worker"
+    )]
+    pub worker_ip: String,
+
+    #[clap(
+        long,
+        env = "HEARTBEAT_INTERVAL",
+        default_value = "10",
+        help = "Interval of heartbeats to the gateway in seconds"
+    )]
+    pub heartbeat_interval: u64,
+
+    #[clap(
+        long,
+        env = "AUTH_METHOD",
+        default_value = "none",
+        value_enum,
+        help = "Authentication method (none, bearer)"
+    )]
+    pub auth_method: AuthMethod,
+
+    #[clap(
+        long,
+        env = "BEARER_TOKEN",
+        requires = "auth_method",
+        help = "Bearer token (required if auth_method=bearer)"
+    )]
+    pub bearer_token: Option<String>,
+
+    #[clap(
+        long,
+        env = "MAX_GRPC_MSG_SIZE",
+        default_value = "1073741824",
+        help = "Max gRPC message size (bytes)"
+    )]
+    pub max_grpc_msg_size: usize,
+
+    #[clap(
+        long,
+        env = "PROVER_COUNT",
+        default_value = "1",
+        help = "Prover count to start"
+    )]
+    pub prover_count: usize,
+
+    #[clap(
+        long,
+        env = "RECURSION_KEYS_DIR",
+        help = "Directory to persist the compress and embed keys, they're rebuilt on restart if not set"
+    )]
+    pub recursion_keys_dir: Option<PathBuf>,
+
+    #[clap(
+        long,
+        env = "APP_CACHE_SIZE",
+        default_value = "4096",
+        help = "Maximum memory (MB) of the fetched app programs and keys, 0 to disable the cache"
+    )]
+    pub app_cache_size: usize,
+}
+
+impl_auth_config!(WorkerConfig);
+
+impl WorkerConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        self.validate_auth()
+    }
 }
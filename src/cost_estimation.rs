@@ -28,8 +28,8 @@ pub struct EstimatedInfo {
 
 pub fn estimate_cost(
     program: Arc<Program>,
-    pk: BaseProvingKey<SC>,
-    vk: BaseVerifyingKey<SC>,
+    pk: Arc<BaseProvingKey<SC>>,
+    vk: Arc<BaseVerifyingKey<SC>>,
     inputs: Option<&[u8]>,
     max_cycles: Option<u64>,
     cost_estimator: bool,
@@ -53,7 +53,11 @@ pub fn estimate_cost(
             None => opts,
         };
         let witness = ProvingWitness::<SC, RiscvChipType<Val>, _>::setup_for_riscv(
-            program, stdin, opts, pk, vk,
+            program,
+            stdin,
+            opts,
+            pk.as_ref().clone(),
+            vk.as_ref().clone(),
         );
 
         let (reports, total_cycles, pv_stream) = emulate_snapshot_pipeline(&witness, |_, _| {})?;
@@ -70,14 +74,10 @@ pub fn estimate_cost(
             total_cycles
         };
 
-        let mut pv_digest = U256::from_be_bytes(sha256(&pv_stream));
-        let mask = (U256::ONE << 253) - U256::ONE;
-        pv_digest &= mask;
-
         Ok(EstimatedInfo {
             cost,
             total_cycles,
-            pv_digest,
+            pv_digest: pv_digest(&pv_stream),
         })
     });
 
@@ -90,6 +90,15 @@ pub fn estimate_cost(
     }
 }
 
+// sha256 of the public values stream masked to 253 bits, it's verified as a public input of the
+// on-chain proof
+pub fn pv_digest(pv_stream: &[u8]) -> U256 {
+    let pv_digest = U256::from_be_bytes(sha256(pv_stream));
+    let mask = (U256::ONE << 253) - U256::ONE;
+
+    pv_digest & mask
+}
+
 fn sha256(bytes: &[u8]) -> [u8; 32] {
     let mut hasher = Sha256::new();
     hasher.update(bytes);
@@ -1,4 +1,4 @@
-use crate::{ErrCode, ErrMsg, EstimateCostResponse};
+use crate::{ErrCode, ErrMsg, EstimateCostResponse, RegisterAppResponse};
 use pico_vm::emulator::riscv::emulator::EmulationError;
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
@@ -9,6 +9,14 @@ pub enum PicoError {
     #[error("input exceeded cycle limit of {0}")]
     ExceededCycleLimit(u64),
 
+    // application ELF is malformed or unsupported
+    #[error("invalid elf: {0}")]
+    InvalidElf(String),
+
+    // application has been registered or is registering
+    #[error("already exists: {0}")]
+    AlreadyExists(String),
+
     // common internal error
     #[error("internal error: {0}")]
     InternalError(String),
@@ -37,7 +45,9 @@ impl From<PicoError> for EstimateCostResponse {
                     pv_digest: vec![],
                 }
             }
-            PicoError::InternalError(_) => {
+            PicoError::InvalidElf(_)
+            | PicoError::AlreadyExists(_)
+            | PicoError::InternalError(_) => {
                 let err = Some(ErrMsg {
                     code: ErrCode::Internal.into(),
                     msg: Some(e.to_string()),
@@ -51,3 +61,23 @@ impl From<PicoError> for EstimateCostResponse {
         }
     }
 }
+
+impl From<PicoError> for RegisterAppResponse {
+    fn from(e: PicoError) -> Self {
+        let code = match e {
+            PicoError::InvalidElf(_) => ErrCode::Inval,
+            PicoError::AlreadyExists(_) => ErrCode::AlreadyExists,
+            PicoError::ExceededCycleLimit(_) | PicoError::InternalError(_) => ErrCode::Internal,
+        };
+        let err = Some(ErrMsg {
+            code: code.into(),
+            msg: Some(e.to_string()),
+        });
+
+        Self {
+            err,
+            app_id: String::new(),
+            registration_id: String::new(),
+        }
+    }
+}
@@ -1,18 +1,39 @@
 use super::config::ServiceConfig;
 use crate::{
-    EstimateCostRequest, EstimateCostResponse, GetProvingResultRequest, GetProvingResultResponse,
-    ProveTaskRequest, ProveTaskResponse, RegisterAppRequest, RegisterAppResponse,
-    app_manager::AppManager,
+    AppStatus, CancelTaskRequest, CancelTaskResponse, DeleteAppRequest, DeleteAppResponse, ErrCode,
+    ErrMsg, EstimateCostRequest, EstimateCostResponse, GetAppRequest, GetAppResponse,
+    GetIntermediateProofRequest, GetIntermediateProofResponse, GetProvingResultRequest,
+    GetProvingResultResponse, GetTaskStatusRequest, GetTaskStatusResponse,
+    GetVerificationParamsRequest, GetVerificationParamsResponse, ListAppsRequest, ListAppsResponse,
+    ProofFormat, ProveTaskRequest, ProveTaskResponse, RegisterAppRequest, RegisterAppResponse,
+    TaskStatus, UpdateAppInfoRequest, UpdateAppInfoResponse, VerifyProofRequest,
+    VerifyProofResponse, WatchTaskRequest, WatchTaskResponse, WrapType,
+    app_manager::{App, AppDeletion, AppManager, AppRegistration, normalize_app_id},
     cost_estimation::estimate_cost,
     prover_network_server::{ProverNetwork, ProverNetworkServer},
-    proving_queue::{ProvingKey, ProvingOutputs, ProvingTask},
-    types::DbPool,
+    proving::{
+        onchain::{Groth16Vk, PublicInputs, format_proof, groth16_verifier},
+        worker::prover::get_vk_root,
+    },
+    proving_queue::{ProvingKey, ProvingOutputs, ProvingTask, TaskCancellations},
+    scheduler::TaskScheduler,
+    task_manager::{
+        SubmittedTaskRow, TaskEvent, TaskEventBroadcaster, TaskEventSender, TaskInsertion,
+        TaskManager, TaskRow,
+    },
+    types::{DbPool, SC},
     utils::auth::AuthConfig,
 };
 use anyhow::Result;
-use crossbeam::channel::Sender;
-use std::sync::Arc;
-use tokio::{signal::ctrl_c, task::JoinHandle};
+use futures::stream::{self, BoxStream, StreamExt};
+use p3_field::PrimeField32;
+use pico_vm::{instances::compiler::vk_merkle::HasStaticVkManager, machine::keys::HashableKey};
+use sqlx::FromRow;
+use std::{
+    sync::Arc,
+    time::{SystemTime, UNIX_EPOCH},
+};
+use tokio::{signal::ctrl_c, sync::broadcast::error::RecvError, task::JoinHandle};
 use tonic::{
     Request, Response, Status, async_trait,
     codec::CompressionEncoding,
@@ -22,31 +43,71 @@ use tonic::{
 use tonic_web::GrpcWebLayer;
 use tower::ServiceBuilder;
 use tower_http::cors::{Any, CorsLayer};
-use tracing::info;
+use tracing::{info, warn};
+
+// default and maximum number of apps returned by `ListApps`
+const DEFAULT_LIST_APPS_LIMIT: u64 = 100;
+const MAX_LIST_APPS_LIMIT: u64 = 1000;
+
+// proving result stored in the `proofs` table, the public values are missing for the proofs
+// generated by the earlier versions
+#[derive(FromRow)]
+struct ProofRow {
+    proof: Option<Vec<u8>>,
+    status: String,
+    error: Option<String>,
+    pv_stream: Option<Vec<u8>>,
+    pv_digest: Option<Vec<u8>>,
+    vk_digest: Option<String>,
+    wrap: Option<String>,
+}
+
+impl ProofRow {
+    // the proofs generated by the earlier versions are groth16
+    fn wrap(&self) -> WrapType {
+        self.wrap
+            .as_deref()
+            .and_then(WrapType::from_str_name)
+            .unwrap_or(WrapType::Groth16)
+    }
+}
 
 pub struct GrpcService {
     cfg: ServiceConfig,
     app_manager: AppManager,
+    task_manager: TaskManager,
     db_pool: Arc<DbPool>,
     outputs: Arc<ProvingOutputs>,
-    sender: Arc<Sender<ProvingTask>>,
+    scheduler: Arc<TaskScheduler>,
+    cancellations: Arc<TaskCancellations>,
+    event_sender: TaskEventSender,
+    event_broadcaster: TaskEventBroadcaster,
 }
 
 impl GrpcService {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         cfg: ServiceConfig,
         db_pool: Arc<DbPool>,
+        app_manager: AppManager,
         outputs: Arc<ProvingOutputs>,
-        sender: Arc<Sender<ProvingTask>>,
+        scheduler: Arc<TaskScheduler>,
+        cancellations: Arc<TaskCancellations>,
+        event_sender: TaskEventSender,
+        event_broadcaster: TaskEventBroadcaster,
     ) -> Self {
-        let app_manager = AppManager::new(db_pool.clone());
+        let task_manager = TaskManager::new(db_pool.clone());
 
         Self {
             cfg,
             app_manager,
+            task_manager,
             db_pool,
             outputs,
-            sender,
+            scheduler,
+            cancellations,
+            event_sender,
+            event_broadcaster,
         }
     }
 
@@ -94,8 +155,228 @@ impl GrpcService {
     }
 }
 
+impl GrpcService {
+    // add a new task, return the existing result if the same task has been submitted
+    #[allow(clippy::too_many_arguments)]
+    async fn add_task(
+        &self,
+        key: &ProvingKey,
+        inputs: Option<&[u8]>,
+        use_gpu: bool,
+        priority: u32,
+        deadline: Option<u64>,
+        wrap: WrapType,
+        keep_intermediate_proofs: bool,
+    ) -> Result<Option<ProveTaskResponse>, Status> {
+        let insertion = self
+            .task_manager
+            .add_task(
+                key,
+                inputs,
+                use_gpu,
+                priority,
+                deadline,
+                wrap,
+                keep_intermediate_proofs,
+            )
+            .await
+            .map_err(|e| Status::internal(format!("failed to save task: {e}")))?;
+        match insertion {
+            TaskInsertion::Inserted => return Ok(None),
+            TaskInsertion::AppNotFound => {
+                return Err(Status::not_found(format!(
+                    "cannot find app {}",
+                    key.app_id()
+                )));
+            }
+            TaskInsertion::Submitted => (),
+        }
+
+        let (submitted, task) = self.get_submitted_task(key).await?;
+        let status = task.status();
+        // the deadline is ignored since it's usually computed from the submission time
+        let mismatch = if submitted.inputs.as_deref() != inputs {
+            Some("inputs")
+        } else if submitted.use_gpu != use_gpu {
+            Some("use_gpu")
+        } else if submitted.priority != priority {
+            Some("priority")
+        } else if submitted.wrap() != wrap {
+            Some("wrap")
+        } else if submitted.keep_intermediate_proofs != keep_intermediate_proofs {
+            Some("keep_intermediate_proofs")
+        } else {
+            None
+        };
+        if let Some(field) = mismatch {
+            info!(
+                "[grpc] task {:?} has been submitted with different {}",
+                key, field
+            );
+            let err = ErrMsg {
+                code: ErrCode::AlreadyExists.into(),
+                msg: Some(format!(
+                    "task {} has been submitted with different `{field}`, set `force_reprove` to \
+                     prove again",
+                    key.task_id(),
+                )),
+            };
+
+            return Ok(Some(ProveTaskResponse {
+                err: Some(err),
+                status: status.into(),
+                proof: None,
+            }));
+        }
+
+        info!(
+            "[grpc] task {:?} has been submitted, status: {:?}",
+            key, status
+        );
+        let proof = if status == TaskStatus::Succeeded {
+            self.get_proof(key).await?
+        } else {
+            None
+        };
+
+        Ok(Some(ProveTaskResponse {
+            err: None,
+            status: status.into(),
+            proof,
+        }))
+    }
+
+    // replace the task for re-proving, it's rejected if the task is still in-progress
+    #[allow(clippy::too_many_arguments)]
+    async fn replace_task(
+        &self,
+        key: &ProvingKey,
+        inputs: Option<&[u8]>,
+        use_gpu: bool,
+        priority: u32,
+        deadline: Option<u64>,
+        wrap: WrapType,
+        keep_intermediate_proofs: bool,
+    ) -> Result<Option<ProveTaskResponse>, Status> {
+        if let Some(task) = self
+            .task_manager
+            .get_task(key)
+            .await
+            .map_err(|e| Status::internal(format!("failed to get task: {e}")))?
+        {
+            let status = task.status();
+            if !status.is_finished() {
+                let err = ErrMsg {
+                    code: ErrCode::Inval.into(),
+                    msg: Some(format!("task {} is still in-progress", key.task_id())),
+                };
+
+                return Ok(Some(ProveTaskResponse {
+                    err: Some(err),
+                    status: status.into(),
+                    proof: None,
+                }));
+            }
+        }
+
+        info!("[grpc] force to prove task {:?} again", key);
+        self.outputs.remove(key);
+        let insertion = self
+            .task_manager
+            .replace_task(
+                key,
+                inputs,
+                use_gpu,
+                priority,
+                deadline,
+                wrap,
+                keep_intermediate_proofs,
+            )
+            .await
+            .map_err(|e| Status::internal(format!("failed to save task: {e}")))?;
+        if insertion == TaskInsertion::AppNotFound {
+            return Err(Status::not_found(format!(
+                "cannot find app {}",
+                key.app_id()
+            )));
+        }
+
+        Ok(None)
+    }
+
+    // get an app for cost estimating or proving, it fails if the app is not ready
+    async fn get_ready_app(&self, app_id: &str) -> Result<App, Status> {
+        if let Some(app) = self
+            .app_manager
+            .get_app(app_id)
+            .await
+            .map_err(|e| Status::internal(format!("failed to get app: {e}")))?
+        {
+            return Ok(app);
+        }
+
+        let status = self
+            .app_manager
+            .get_app_status(app_id)
+            .await
+            .map_err(|e| Status::internal(format!("failed to get app status: {e}")))?;
+        let status = match status {
+            AppStatus::Registering => {
+                Status::failed_precondition(format!("app {app_id} is still registering"))
+            }
+            AppStatus::Unknown | AppStatus::Ready | AppStatus::Failed => {
+                Status::not_found(format!("cannot find app {app_id}"))
+            }
+        };
+
+        Err(status)
+    }
+
+    async fn get_submitted_task(
+        &self,
+        key: &ProvingKey,
+    ) -> Result<(SubmittedTaskRow, TaskRow), Status> {
+        let submitted = self
+            .task_manager
+            .get_submitted_task(key)
+            .await
+            .map_err(|e| Status::internal(format!("failed to get task: {e}")))?;
+        let task = self
+            .task_manager
+            .get_task(key)
+            .await
+            .map_err(|e| Status::internal(format!("failed to get task: {e}")))?;
+
+        submitted
+            .zip(task)
+            .ok_or_else(|| Status::not_found(format!("cannot find task {key:?}")))
+    }
+
+    // get the generated proof from memory or database, the memory entry is kept for fetching
+    async fn get_proof(&self, key: &ProvingKey) -> Result<Option<Vec<u8>>, Status> {
+        if let Some(output) = self.outputs.get(key) {
+            return Ok(Some(output.proof.to_vec()));
+        }
+
+        let proof = sqlx::query_as::<_, (Option<Vec<u8>>,)>(
+            "SELECT proof FROM proofs WHERE app_id = ? AND task_id = ? AND status = ?",
+        )
+        .bind(key.app_id())
+        .bind(key.task_id())
+        .bind(TaskStatus::Succeeded.as_str_name())
+        .fetch_optional(&*self.db_pool)
+        .await
+        .map_err(|e| Status::internal(format!("failed to get proof from database: {e}")))?
+        .and_then(|row| row.0);
+
+        Ok(proof)
+    }
+}
+
 #[async_trait]
 impl ProverNetwork for GrpcService {
+    type WatchTaskStream = BoxStream<'static, Result<WatchTaskResponse, Status>>;
+
     // register a new application with elf
     async fn register_app(
         &self,
@@ -104,16 +385,263 @@ impl ProverNetwork for GrpcService {
         info!("receive RegisterAppRequest");
 
         let req = req.into_inner();
-        let app = self
+        let res = match self.app_manager.register_app(req.elf, req.info).await {
+            Ok(AppRegistration::Registering { registration_id }) => RegisterAppResponse {
+                err: None,
+                app_id: String::new(),
+                registration_id,
+            },
+            // return the app ID of the registered ELF with the error
+            Ok(AppRegistration::Registered {
+                registration_id,
+                app_id,
+            }) => RegisterAppResponse {
+                err: Some(ErrMsg {
+                    code: ErrCode::AlreadyExists.into(),
+                    msg: Some(format!(
+                        "elf {registration_id} has been registered as app {app_id}"
+                    )),
+                }),
+                app_id,
+                registration_id,
+            },
+            Err(e) => {
+                warn!("[grpc] failed to register app: {e}");
+                e.into()
+            }
+        };
+
+        info!("return RegisterAppResponse");
+
+        Ok(Response::new(res))
+    }
+
+    // get the registration status of an application
+    async fn get_app(
+        &self,
+        req: Request<GetAppRequest>,
+    ) -> Result<Response<GetAppResponse>, Status> {
+        info!("receive GetAppRequest");
+
+        let req = req.into_inner();
+        let (status, failure_reason, app_id) = match req.registration_id {
+            Some(registration_id) => {
+                let registration = self
+                    .app_manager
+                    .get_registration(&registration_id)
+                    .await
+                    .map_err(|e| Status::internal(format!("failed to get registration: {e}")))?;
+                if registration.status == AppStatus::Unknown {
+                    return Err(Status::not_found(format!(
+                        "cannot find registration {registration_id}"
+                    )));
+                }
+                (
+                    registration.status,
+                    registration.failure_reason,
+                    registration.app_id.unwrap_or_default(),
+                )
+            }
+            None => {
+                let app_id = normalize_app_id(&req.app_id).to_string();
+                let status = self
+                    .app_manager
+                    .get_app_status(&app_id)
+                    .await
+                    .map_err(|e| Status::internal(format!("failed to get app status: {e}")))?;
+                if status == AppStatus::Unknown {
+                    return Err(Status::not_found(format!("cannot find app {app_id}")));
+                }
+                (status, None, app_id)
+            }
+        };
+
+        let (app, vk) = if status == AppStatus::Ready {
+            let app = self
+                .app_manager
+                .get_app_meta(&app_id)
+                .await
+                .map_err(|e| Status::internal(format!("failed to get app: {e}")))?;
+            let vk = self
+                .app_manager
+                .get_app_vk(&app_id)
+                .await
+                .map_err(|e| Status::internal(format!("failed to get app: {e}")))?;
+            (app.map(Into::into), vk)
+        } else {
+            (None, None)
+        };
+
+        info!("return GetAppResponse");
+
+        Ok(Response::new(GetAppResponse {
+            err: None,
+            status: status.into(),
+            failure_reason,
+            app,
+            vk,
+            app_id,
+        }))
+    }
+
+    // list the registered applications
+    async fn list_apps(
+        &self,
+        req: Request<ListAppsRequest>,
+    ) -> Result<Response<ListAppsResponse>, Status> {
+        info!("receive ListAppsRequest");
+
+        let req = req.into_inner();
+        let offset = req.offset.unwrap_or(0);
+        let limit = req
+            .limit
+            .unwrap_or(DEFAULT_LIST_APPS_LIMIT)
+            .min(MAX_LIST_APPS_LIMIT);
+        let (apps, total) = self
             .app_manager
-            .set_app(&req.elf, req.info)
+            .list_apps(offset, limit)
             .await
-            .map_err(|e| Status::internal(format!("failed to register app: {e}")))?;
-        let app_id = app.app_id;
+            .map_err(|e| Status::internal(format!("failed to list apps: {e}")))?;
 
-        info!("return RegisterAppResponse");
+        info!("return ListAppsResponse");
+
+        Ok(Response::new(ListAppsResponse {
+            err: None,
+            apps: apps.into_iter().map(Into::into).collect(),
+            total,
+        }))
+    }
+
+    // update the program information of an application
+    async fn update_app_info(
+        &self,
+        req: Request<UpdateAppInfoRequest>,
+    ) -> Result<Response<UpdateAppInfoResponse>, Status> {
+        info!("receive UpdateAppInfoRequest");
 
-        Ok(Response::new(RegisterAppResponse { err: None, app_id }))
+        let req = req.into_inner();
+        let app_id = normalize_app_id(&req.app_id).to_string();
+        let updated = self
+            .app_manager
+            .update_app_info(&app_id, req.info)
+            .await
+            .map_err(|e| Status::internal(format!("failed to update app: {e}")))?;
+        if !updated {
+            return Err(Status::not_found(format!("cannot find app {app_id}")));
+        }
+
+        info!("return UpdateAppInfoResponse");
+
+        Ok(Response::new(UpdateAppInfoResponse { err: None }))
+    }
+
+    // delete an application without pending tasks
+    async fn delete_app(
+        &self,
+        req: Request<DeleteAppRequest>,
+    ) -> Result<Response<DeleteAppResponse>, Status> {
+        info!("receive DeleteAppRequest");
+
+        let req = req.into_inner();
+        let app_id = normalize_app_id(&req.app_id).to_string();
+        let delete_proofs = req.delete_proofs.unwrap_or(false);
+        let deletion = self
+            .app_manager
+            .delete_app(&app_id, delete_proofs)
+            .await
+            .map_err(|e| Status::internal(format!("failed to delete app: {e}")))?;
+
+        let msg = match deletion {
+            AppDeletion::Deleted => None,
+            AppDeletion::NotFound => {
+                return Err(Status::not_found(format!("cannot find app {app_id}")));
+            }
+            AppDeletion::Registering => Some(format!("app {app_id} is still registering")),
+            AppDeletion::PendingTasks(count) => Some(format!(
+                "app {app_id} has {count} queued or in-progress tasks"
+            )),
+        };
+        let err = msg.map(|msg| ErrMsg {
+            code: ErrCode::Inval.into(),
+            msg: Some(msg),
+        });
+
+        // the proofs kept in memory are deleted with the app
+        if err.is_none() && delete_proofs {
+            self.outputs.retain(|key, _| key.app_id() != app_id);
+        }
+
+        info!("return DeleteAppResponse");
+
+        Ok(Response::new(DeleteAppResponse { err }))
+    }
+
+    // get the parameters for verifying the proofs of an application on-chain
+    async fn get_verification_params(
+        &self,
+        req: Request<GetVerificationParamsRequest>,
+    ) -> Result<Response<GetVerificationParamsResponse>, Status> {
+        info!("receive GetVerificationParamsRequest");
+
+        let app_id = normalize_app_id(&req.into_inner().app_id).to_string();
+        let app = self.get_ready_app(&app_id).await?;
+
+        let vk_manager = <SC as HasStaticVkManager>::static_vk_manager();
+        let vk_root = get_vk_root(vk_manager)
+            .iter()
+            .map(|v| v.as_canonical_u32())
+            .collect();
+        let verifier = groth16_verifier(&self.cfg.gnark_config().download_path)
+            .map_err(|e| Status::internal(format!("failed to get groth16 verifier: {e}")))?;
+
+        info!("return GetVerificationParamsResponse");
+
+        Ok(Response::new(GetVerificationParamsResponse {
+            err: None,
+            vk_digest: app.vk.hash_str_via_bn254(),
+            vk_root,
+            groth16_vk_hash: verifier.vk_hash,
+            verifier_contract: verifier.contract,
+        }))
+    }
+
+    // verify a groth16 proof with the public values of an application
+    async fn verify_proof(
+        &self,
+        req: Request<VerifyProofRequest>,
+    ) -> Result<Response<VerifyProofResponse>, Status> {
+        info!("receive VerifyProofRequest");
+
+        let req = req.into_inner();
+        let app_id = normalize_app_id(&req.app_id).to_string();
+        let app = self.get_ready_app(&app_id).await?;
+        let vk = Groth16Vk::load(&self.cfg.gnark_config().download_path)
+            .map_err(|e| Status::internal(format!("failed to load groth16 vk: {e}")))?;
+
+        let res = match PublicInputs::new(&app.vk.hash_str_via_bn254(), &req.pv_stream)
+            .and_then(|inputs| vk.verify(&req.proof, &inputs))
+        {
+            Ok(()) => VerifyProofResponse {
+                err: None,
+                valid: true,
+            },
+            Err(e) => {
+                info!("[grpc] proof of app {app_id} is invalid: {e}");
+                let err = ErrMsg {
+                    code: ErrCode::Inval.into(),
+                    msg: Some(format!("invalid proof: {e}")),
+                };
+
+                VerifyProofResponse {
+                    err: Some(err),
+                    valid: false,
+                }
+            }
+        };
+
+        info!("return VerifyProofResponse");
+
+        Ok(Response::new(res))
     }
 
     // estimate gas cost
@@ -124,13 +652,8 @@ impl ProverNetwork for GrpcService {
         info!("receive EstimateCostRequest");
 
         let req = req.into_inner();
-        let app_id = req.app_id;
-        let app = self
-            .app_manager
-            .get_app(&app_id)
-            .await
-            .map_err(|e| Status::internal(format!("failed to get app: {e}")))?
-            .ok_or_else(|| Status::not_found(format!("cannot find app {app_id}")))?;
+        let app_id = normalize_app_id(&req.app_id).to_string();
+        let app = self.get_ready_app(&app_id).await?;
 
         let res = match estimate_cost(
             app.program,
@@ -161,32 +684,113 @@ impl ProverNetwork for GrpcService {
         info!("receive ProveTaskRequest");
 
         let req = req.into_inner();
-        let app_id = req.app_id;
-        let app = self
-            .app_manager
-            .get_app(&app_id)
-            .await
-            .map_err(|e| Status::internal(format!("failed to get app: {e}")))?
-            .ok_or_else(|| Status::not_found(format!("cannot find app {app_id}")))?;
+        let app_id = normalize_app_id(&req.app_id).to_string();
+        let app = self.get_ready_app(&app_id).await?;
 
         let key = ProvingKey::new(app_id, req.task_id);
         // Default to cpu if not specified
         let use_gpu = req.use_gpu.unwrap_or(false);
+        let force_reprove = req.force_reprove.unwrap_or(false);
+        let priority = req.priority.unwrap_or(0);
+        let wrap = req.wrap();
+        let keep_intermediate_proofs = req.keep_intermediate_proofs.unwrap_or(false);
+
+        // reject the task which could never be proved in time
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        if req.deadline.is_some_and(|deadline| deadline <= now) {
+            let err = ErrMsg {
+                code: ErrCode::Inval.into(),
+                msg: Some(format!("deadline of task {} has expired", key.task_id())),
+            };
+
+            return Ok(Response::new(ProveTaskResponse {
+                err: Some(err),
+                status: TaskStatus::Unknown.into(),
+                proof: None,
+            }));
+        }
+        if use_gpu {
+            let err = ErrMsg {
+                code: ErrCode::Inval.into(),
+                msg: Some("GPU proving is not supported by the service".to_string()),
+            };
+
+            return Ok(Response::new(ProveTaskResponse {
+                err: Some(err),
+                status: TaskStatus::Unknown.into(),
+                proof: None,
+            }));
+        }
+        if wrap == WrapType::Plonk && self.cfg.gnark_plonk_url.is_none() {
+            let err = ErrMsg {
+                code: ErrCode::Inval.into(),
+                msg: Some("PLONK wrapping is not enabled by the service".to_string()),
+            };
+
+            return Ok(Response::new(ProveTaskResponse {
+                err: Some(err),
+                status: TaskStatus::Unknown.into(),
+                proof: None,
+            }));
+        }
+
+        // persist the task before scheduling, so it could be restored if the service restarts
+        let res = if force_reprove {
+            self.replace_task(
+                &key,
+                req.inputs.as_deref(),
+                use_gpu,
+                priority,
+                req.deadline,
+                wrap,
+                keep_intermediate_proofs,
+            )
+            .await?
+        } else {
+            self.add_task(
+                &key,
+                req.inputs.as_deref(),
+                use_gpu,
+                priority,
+                req.deadline,
+                wrap,
+                keep_intermediate_proofs,
+            )
+            .await?
+        };
+        if let Some(res) = res {
+            info!("return ProveTaskResponse for the submitted task {:?}", key);
+            return Ok(Response::new(res));
+        }
+
+        // the status has been saved, it's only published to the watchers
+        let _ = self
+            .event_sender
+            .send((key.clone(), TaskEvent::Status(TaskStatus::Queued)));
+
         let task = ProvingTask::new(
             key,
             app.program,
-            Arc::new(app.pk),
-            Arc::new(app.vk),
+            app.pk,
+            app.vk,
             req.inputs,
             use_gpu,
+            req.deadline,
+            wrap,
+            keep_intermediate_proofs,
         );
-        self.sender
-            .send(task)
-            .map_err(|e| Status::internal(format!("failed to send a proving task: {e}")))?;
+        self.scheduler.push(task, priority);
 
         info!("return ProveTaskResponse");
 
-        Ok(Response::new(ProveTaskResponse { err: None }))
+        Ok(Response::new(ProveTaskResponse {
+            err: None,
+            status: TaskStatus::Queued.into(),
+            proof: None,
+        }))
     }
 
     // try to fetch the proving result if complete
@@ -197,7 +801,8 @@ impl ProverNetwork for GrpcService {
         info!("receive GetProvingResultRequest");
 
         let req = req.into_inner();
-        let key = ProvingKey::new(req.app_id, req.task_id);
+        let proof_format = req.proof_format();
+        let key = ProvingKey::new(normalize_app_id(&req.app_id).to_string(), req.task_id);
 
         info!("[grpc] looking for proof with key: {:?}", key);
 
@@ -205,42 +810,309 @@ impl ProverNetwork for GrpcService {
         info!("[grpc] checking memory for key: {:?}", key);
         info!("[grpc] current memory entries: {}", self.outputs.len());
 
-        let proof = if let Some((_, output)) = self.outputs.remove(&key) {
+        let (proof, err) = if let Some((_, output)) = self.outputs.remove(&key) {
             info!(
                 "[grpc] found proof in memory, size: {} bytes",
                 output.proof.len()
             );
-            Some(output.proof)
+            let proof = ProofRow {
+                proof: Some(output.proof.to_vec()),
+                status: TaskStatus::Succeeded.as_str_name().to_string(),
+                error: None,
+                pv_stream: Some(output.pv_stream.to_vec()),
+                pv_digest: Some(output.pv_digest),
+                vk_digest: Some(output.vk_digest),
+                wrap: Some(output.wrap.as_str_name().to_string()),
+            };
+
+            (Some(proof), None)
         } else {
             info!("[grpc] proof not in memory, checking database");
             // If not in memory, try to get from database
-            let db_proof = sqlx::query_as::<_, (Option<Vec<u8>>,)>(
-                "SELECT proof FROM proofs WHERE app_id = ? AND task_id = ?",
+            let row = sqlx::query_as::<_, ProofRow>(
+                "SELECT proof, status, error, pv_stream, pv_digest, vk_digest, wrap \
+                 FROM proofs WHERE app_id = ? AND task_id = ?",
             )
             .bind(&key.app_id())
             .bind(&key.task_id())
             .fetch_optional(&*self.db_pool)
             .await
-            .map_err(|e| Status::internal(format!("failed to get proof from database: {e}")))?
-            .and_then(|row| row.0);
+            .map_err(|e| Status::internal(format!("failed to get proof from database: {e}")))?;
 
-            if let Some(ref proof_data) = db_proof {
-                info!(
-                    "[grpc] found proof in database, size: {} bytes",
-                    proof_data.len()
-                );
-            } else {
-                info!("[grpc] proof not found in database");
-            }
+            match row {
+                Some(row) if row.status == TaskStatus::Failed.as_str_name() => {
+                    info!("[grpc] found proving failure in database: {:?}", row.error);
+                    let err = ErrMsg {
+                        code: ErrCode::ProvingFailed.into(),
+                        msg: row.error,
+                    };
+
+                    (None, Some(err))
+                }
+                Some(row) if row.status == TaskStatus::Cancelled.as_str_name() => {
+                    info!("[grpc] found cancelled task in database");
+                    let err = ErrMsg {
+                        code: ErrCode::TaskCancelled.into(),
+                        msg: row.error,
+                    };
 
-            db_proof.map(Arc::from)
+                    (None, Some(err))
+                }
+                Some(row) => {
+                    if let Some(ref proof_data) = row.proof {
+                        info!(
+                            "[grpc] found proof in database, size: {} bytes",
+                            proof_data.len()
+                        );
+                    }
+
+                    (Some(row), None)
+                }
+                None => {
+                    info!("[grpc] proof not found in database");
+                    (None, None)
+                }
+            }
         };
 
         info!("return GetProvingResultResponse");
 
-        Ok(Response::new(GetProvingResultResponse {
+        let res = match proof {
+            // the STARK, embed and plonk proofs are only returned in raw
+            Some(row) if row.wrap() != WrapType::Groth16 && proof_format != ProofFormat::Raw => {
+                let err = ErrMsg {
+                    code: ErrCode::Inval.into(),
+                    msg: Some(format!(
+                        "proof format {:?} is not supported by the {:?} proof",
+                        proof_format,
+                        row.wrap(),
+                    )),
+                };
+
+                GetProvingResultResponse {
+                    err: Some(err),
+                    ..Default::default()
+                }
+            }
+            Some(row) => {
+                let wrap = row.wrap();
+                let proof = match wrap {
+                    WrapType::Groth16 => row
+                        .proof
+                        .map(|proof| format_proof(&proof, proof_format))
+                        .transpose()
+                        .map_err(|e| Status::internal(format!("failed to format proof: {e}")))?,
+                    _ => row.proof,
+                };
+
+                GetProvingResultResponse {
+                    err,
+                    proof,
+                    pv_stream: row.pv_stream,
+                    pv_digest: row.pv_digest,
+                    vk_digest: row.vk_digest,
+                    wrap: Some(wrap.into()),
+                }
+            }
+            None => GetProvingResultResponse {
+                err,
+                ..Default::default()
+            },
+        };
+
+        Ok(Response::new(res))
+    }
+
+    // download a persisted compress or embed proof of a proved task
+    async fn get_intermediate_proof(
+        &self,
+        req: Request<GetIntermediateProofRequest>,
+    ) -> Result<Response<GetIntermediateProofResponse>, Status> {
+        info!("receive GetIntermediateProofRequest");
+
+        let req = req.into_inner();
+        let stage = req.stage();
+        let key = ProvingKey::new(normalize_app_id(&req.app_id).to_string(), req.task_id);
+
+        let (proof, vk) = sqlx::query_as::<_, (Vec<u8>, Vec<u8>)>(
+            "SELECT proof, vk FROM intermediate_proofs \
+             WHERE app_id = ? AND task_id = ? AND stage = ?",
+        )
+        .bind(key.app_id())
+        .bind(key.task_id())
+        .bind(stage.as_str_name())
+        .fetch_optional(&*self.db_pool)
+        .await
+        .map_err(|e| Status::internal(format!("failed to get intermediate proof: {e}")))?
+        .ok_or_else(|| Status::not_found(format!("cannot find {stage:?} proof of task {key:?}")))?;
+
+        info!("return GetIntermediateProofResponse");
+
+        Ok(Response::new(GetIntermediateProofResponse {
             err: None,
-            proof: proof.map(|arc_proof| arc_proof.to_vec()),
+            proof,
+            vk,
         }))
     }
+
+    // get the lifecycle status and progress of a proving task
+    async fn get_task_status(
+        &self,
+        req: Request<GetTaskStatusRequest>,
+    ) -> Result<Response<GetTaskStatusResponse>, Status> {
+        info!("receive GetTaskStatusRequest");
+
+        let req = req.into_inner();
+        let key = ProvingKey::new(normalize_app_id(&req.app_id).to_string(), req.task_id);
+        let task = self
+            .task_manager
+            .get_task(&key)
+            .await
+            .map_err(|e| Status::internal(format!("failed to get task: {e}")))?;
+
+        // the task which is never submitted is returned as unknown
+        let res = match task {
+            Some(task) => {
+                let mut res: GetTaskStatusResponse = task.into();
+                res.queue_position = self.scheduler.position(&key).map(|p| p as u64);
+                res
+            }
+            None => {
+                info!("[grpc] task {:?} is never submitted", key);
+                GetTaskStatusResponse {
+                    status: TaskStatus::Unknown.into(),
+                    ..Default::default()
+                }
+            }
+        };
+
+        info!("return GetTaskStatusResponse");
+
+        Ok(Response::new(res))
+    }
+
+    // cancel a queued or in-progress proving task
+    async fn cancel_task(
+        &self,
+        req: Request<CancelTaskRequest>,
+    ) -> Result<Response<CancelTaskResponse>, Status> {
+        info!("receive CancelTaskRequest");
+
+        let req = req.into_inner();
+        let key = ProvingKey::new(normalize_app_id(&req.app_id).to_string(), req.task_id);
+
+        let cancelled = self
+            .task_manager
+            .cancel_task(&key)
+            .await
+            .map_err(|e| Status::internal(format!("failed to cancel task: {e}")))?;
+        let res = if cancelled {
+            // remove the task if it's queued, otherwise abort it if it's in proving
+            if self.scheduler.remove(&key) {
+                info!("[grpc] remove the queued task {:?}", key);
+            } else if self.cancellations.cancel(&key) {
+                info!("[grpc] abort the in-progress task {:?}", key);
+            }
+            self.outputs.remove(&key);
+            let _ = self
+                .event_sender
+                .send((key.clone(), TaskEvent::Status(TaskStatus::Cancelled)));
+
+            CancelTaskResponse {
+                err: None,
+                status: TaskStatus::Cancelled.into(),
+            }
+        } else {
+            let task = self
+                .task_manager
+                .get_task(&key)
+                .await
+                .map_err(|e| Status::internal(format!("failed to get task: {e}")))?
+                .ok_or_else(|| Status::not_found(format!("cannot find task {key:?}")))?;
+            let status = task.status();
+            let err = ErrMsg {
+                code: ErrCode::Inval.into(),
+                msg: Some(format!("task {} has finished", key.task_id())),
+            };
+
+            CancelTaskResponse {
+                err: Some(err),
+                status: status.into(),
+            }
+        };
+
+        info!("return CancelTaskResponse");
+
+        Ok(Response::new(res))
+    }
+
+    // watch the progress events of a proving task until it finishes
+    async fn watch_task(
+        &self,
+        req: Request<WatchTaskRequest>,
+    ) -> Result<Response<Self::WatchTaskStream>, Status> {
+        info!("receive WatchTaskRequest");
+
+        let req = req.into_inner();
+        let key = ProvingKey::new(normalize_app_id(&req.app_id).to_string(), req.task_id);
+
+        // subscribe before reading the current status, the events are published after saving
+        let receiver = self.event_broadcaster.subscribe();
+        let task = self
+            .task_manager
+            .get_task(&key)
+            .await
+            .map_err(|e| Status::internal(format!("failed to get task: {e}")))?
+            .ok_or_else(|| Status::not_found(format!("cannot find task {key:?}")))?;
+        let snapshot = WatchTaskResponse::from(&task);
+
+        // stream the events of this task, it ends once the task finishes
+        let db_pool = self.db_pool.clone();
+        let events = stream::unfold((receiver, task.status()), move |(mut receiver, status)| {
+            let key = key.clone();
+            let task_manager = TaskManager::new(db_pool.clone());
+            async move {
+                if status.is_finished() {
+                    return None;
+                }
+
+                loop {
+                    match receiver.recv().await {
+                        Ok((event_key, event)) if event_key == key => {
+                            let res = event.to_response(status);
+                            let status = res.status();
+                            return Some((Ok(res), (receiver, status)));
+                        }
+                        Ok(_) => (),
+                        // the skipped events could include the final one, so the current status is
+                        // re-read and sent as a snapshot, the stream ends if the task has finished
+                        Err(RecvError::Lagged(n)) => {
+                            warn!("[grpc] watcher of task {:?} lagged {} events", key, n);
+                            return match task_manager.get_task(&key).await {
+                                Ok(Some(task)) => {
+                                    let res = WatchTaskResponse::from(&task);
+                                    let status = res.status();
+                                    Some((Ok(res), (receiver, status)))
+                                }
+                                Ok(None) => None,
+                                Err(e) => {
+                                    let e = Status::internal(format!("failed to get task: {e}"));
+                                    Some((Err(e), (receiver, status)))
+                                }
+                            };
+                        }
+                        Err(RecvError::Closed) => return None,
+                    }
+                }
+            }
+        });
+
+        info!("return WatchTaskResponse stream");
+
+        Ok(Response::new(
+            stream::once(async move { Ok(snapshot) })
+                .chain(events)
+                .boxed(),
+        ))
+    }
 }
@@ -5,6 +5,8 @@ pub mod error;
 pub mod grpc;
 pub mod proving;
 pub mod proving_queue;
+pub mod scheduler;
+pub mod task_manager;
 pub mod types;
 pub mod utils;
 
@@ -1,12 +1,13 @@
 use crate::{
     proving::messages::{
-        gateway::GatewayMsg,
-        riscv::{RiscvMsg, RiscvRequest},
+        gateway::{GatewayMsg, TaskId},
+        riscv::{RiscvMsg, RiscvRequest, SharedRecord},
     },
-    proving_queue::ProvingTask,
+    proving_queue::{CancelSignal, ProvingTask},
+    task_manager::{TaskEvent, TaskReporter},
     types::SC,
 };
-use anyhow::Result;
+use anyhow::{Result, anyhow, bail};
 use crossbeam::channel::{Receiver, Sender, bounded};
 use log::{debug, info};
 use p3_koala_bear::KoalaBear;
@@ -15,27 +16,32 @@ use pico_vm::{
     compiler::riscv::program::Program,
     configs::{config::StarkGenericConfig, stark_config::kb_poseidon2::KoalaBearPoseidon2},
     emulator::{emulator::MetaEmulator, opts::EmulatorOpts, stdin::EmulatorStdin},
-    instances::{
-        chiptype::riscv_chiptype::RiscvChipType, compiler::vk_merkle::HasStaticVkManager,
-        configs::riscv_kb_config::StarkConfig as RiscvKBSC, machine::riscv::RiscvMachine,
-    },
-    machine::{machine::MachineBehavior, witness::ProvingWitness},
-    primitives::consts::RISCV_NUM_PVS,
+    instances::{chiptype::riscv_chiptype::RiscvChipType, compiler::vk_merkle::HasStaticVkManager},
+    machine::witness::ProvingWitness,
 };
 use std::{sync::Arc, thread, time::Instant};
 
 pub trait EmulatorRunner: StarkGenericConfig {
-    fn run(task: ProvingTask, gateway_endpoint: Arc<Sender<GatewayMsg>>) -> Result<()>;
+    fn run(
+        task_id: TaskId,
+        task: ProvingTask,
+        gateway_endpoint: Arc<Sender<GatewayMsg>>,
+        cancel: CancelSignal,
+        reporter: TaskReporter,
+    ) -> Result<Vec<u8>>;
 }
 
 impl EmulatorRunner for KoalaBearPoseidon2 {
-    fn run(task: ProvingTask, gateway_endpoint: Arc<Sender<GatewayMsg>>) -> Result<()> {
+    fn run(
+        task_id: TaskId,
+        task: ProvingTask,
+        gateway_endpoint: Arc<Sender<GatewayMsg>>,
+        cancel: CancelSignal,
+        reporter: TaskReporter,
+    ) -> Result<Vec<u8>> {
         // Setups
         let _vk_manager = <KoalaBearPoseidon2 as HasStaticVkManager>::static_vk_manager();
 
-        let riscv_machine =
-            RiscvMachine::new(RiscvKBSC::new(), RiscvChipType::all_chips(), RISCV_NUM_PVS);
-
         // Use the program directly from the task since it's already been preprocessed
         // The program in ProvingTask has already been compiled and preprocessed
         let program = task.program.clone();
@@ -47,16 +53,15 @@ impl EmulatorRunner for KoalaBearPoseidon2 {
         );
         let (stdin, _) = stdin_builder.finalize::<Program>();
 
-        let (pk, vk) = riscv_machine.setup_keys(&program);
-
+        // the keys have been set up when the app is registered
         let riscv_opts = EmulatorOpts::bench_riscv_ops();
         let witness =
             ProvingWitness::<KoalaBearPoseidon2, RiscvChipType<KoalaBear>, _>::setup_for_riscv(
                 program,
                 stdin,
                 riscv_opts,
-                pk.clone(),
-                vk.clone(),
+                task.pk.as_ref().clone(),
+                task.vk.as_ref().clone(),
             );
         // Initialize the emulator.
         let mut emulator = MetaEmulator::setup_riscv(&witness, None);
@@ -71,6 +76,7 @@ impl EmulatorRunner for KoalaBearPoseidon2 {
 
         // Start the emulator thread.
         log_section("RISCV EMULATE PHASE");
+        let emulator_cancel = cancel.clone();
         let emulator_handle = thread::spawn(move || {
             let mut batch_num = 1;
             loop {
@@ -87,11 +93,21 @@ impl EmulatorRunner for KoalaBearPoseidon2 {
                     batch_num,
                     start_local.elapsed(),
                 );
+                reporter.report(TaskEvent::BatchEmulated(batch_num));
 
                 if report.done {
                     break;
                 }
 
+                // stop emulating the next batch if the task is cancelled
+                if emulator_cancel.is_cancelled() {
+                    tracing::debug!(
+                        "--- Stop emulation after batch-{} for cancellation",
+                        batch_num
+                    );
+                    break;
+                }
+
                 batch_num += 1;
             }
 
@@ -106,34 +122,54 @@ impl EmulatorRunner for KoalaBearPoseidon2 {
         let mut chunk_index = 0;
 
         while let Ok(record) = record_receiver.recv() {
+            // drain the remaining records of the current batch if the task is cancelled
+            if cancel.is_cancelled() {
+                continue;
+            }
+
             let req = RiscvRequest {
                 chunk_index,
-                record,
+                record: Arc::new(SharedRecord::new(record)),
             };
 
-            tracing::debug!("send emulation record-{chunk_index}");
+            tracing::debug!("send emulation record-{chunk_index} of task-{task_id}");
             gateway_endpoint.send(GatewayMsg::Riscv(
                 RiscvMsg::Request(req),
-                // TODO: fix to id and ip address
-                chunk_index.to_string(),
+                task_id,
+                // TODO: fix to ip address
                 "".to_string(),
             ))?;
 
             chunk_index += 1;
         }
 
+        let mut emulator = emulator_handle
+            .join()
+            .map_err(|_| anyhow!("emulator thread panicked"))?;
+        if cancel.is_cancelled() {
+            bail!("task cancelled");
+        }
+
         // send the emulator complete message
-        gateway_endpoint.send(GatewayMsg::EmulatorComplete)?;
+        gateway_endpoint.send(GatewayMsg::EmulatorComplete(task_id))?;
 
-        let emulator = emulator_handle.join().unwrap();
         info!("Total Cycles: {}", emulator.cycles());
 
-        Ok(())
+        // committed public values of the program, the embed proof is bound to its digest
+        Ok(emulator.get_pv_stream())
     }
 }
 
-pub fn run(task: ProvingTask, gateway_endpoint: Arc<Sender<GatewayMsg>>) {
-    debug!("[coordinator] emulator init");
-    SC::run(task.clone(), gateway_endpoint.clone()).unwrap();
-    debug!("[coordinator] emulator run completed");
+pub fn run(
+    task_id: TaskId,
+    task: ProvingTask,
+    gateway_endpoint: Arc<Sender<GatewayMsg>>,
+    cancel: CancelSignal,
+    reporter: TaskReporter,
+) -> Result<Vec<u8>> {
+    debug!("[coordinator] emulator init for task-{task_id}");
+    let pv_stream = SC::run(task_id, task, gateway_endpoint, cancel, reporter)?;
+    debug!("[coordinator] emulator run completed for task-{task_id}");
+
+    Ok(pv_stream)
 }
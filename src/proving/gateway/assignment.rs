@@ -0,0 +1,373 @@
+use crate::proving::messages::{
+    combine::CombineMsg,
+    gateway::{ChunkFailure, ChunkKey, GatewayMsg, IpAddr, TaskId},
+    riscv::RiscvMsg,
+};
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+#[derive(Default)]
+struct WorkerAssignments {
+    // last heartbeat of a remote worker, the local provers never send heartbeats and are closed
+    // once their threads exit
+    last_heartbeat: Option<Instant>,
+    requests: HashMap<ChunkKey, GatewayMsg>,
+    // lease deadlines of the requests pulled by a remote worker but not acknowledged yet
+    leases: HashMap<ChunkKey, Instant>,
+}
+
+/// Requests assigned to each worker by its IP address. The outstanding requests of a closed or
+/// dead worker are returned for re-dispatching.
+pub struct Assignments {
+    // requests dispatched but not taken by any worker yet, the riscv records are shared with the
+    // dispatched messages
+    dispatched: HashMap<ChunkKey, GatewayMsg>,
+    workers: HashMap<IpAddr, WorkerAssignments>,
+    // a remote worker is dead if no heartbeat is received in this duration
+    heartbeat_timeout: Duration,
+    // a pulled request is re-dispatched if it's not acknowledged in this duration
+    lease_timeout: Duration,
+}
+
+impl Assignments {
+    pub fn new(heartbeat_timeout: Duration, lease_timeout: Duration) -> Self {
+        Self {
+            dispatched: HashMap::new(),
+            workers: HashMap::new(),
+            heartbeat_timeout,
+            lease_timeout,
+        }
+    }
+
+    // keep a request before it's sent to the workers
+    pub fn dispatch(&mut self, msg: &GatewayMsg) {
+        if let Some(key) = ChunkKey::of_request(msg) {
+            self.dispatched.insert(key, msg.clone());
+        }
+    }
+
+    // move a dispatched request to the worker which takes it
+    pub fn assign(&mut self, key: ChunkKey, ip_addr: IpAddr) {
+        if let Some(msg) = self.dispatched.remove(&key) {
+            self.workers
+                .entry(ip_addr.clone())
+                .or_default()
+                .requests
+                .insert(key, msg.with_ip_addr(ip_addr));
+        }
+    }
+
+    // assign a request pulled by a remote worker, the pulling response could be lost, so it's
+    // only held by the worker until the lease expires unless it's acknowledged
+    pub fn lease(&mut self, key: ChunkKey, ip_addr: IpAddr) {
+        if self.dispatched.contains_key(&key) {
+            self.assign(key, ip_addr.clone());
+            let deadline = Instant::now() + self.lease_timeout;
+            if let Some(worker) = self.workers.get_mut(&ip_addr) {
+                worker.leases.insert(key, deadline);
+            }
+        }
+    }
+
+    pub fn ack(&mut self, key: ChunkKey, ip_addr: &str) {
+        if let Some(worker) = self.workers.get_mut(ip_addr) {
+            worker.leases.remove(&key);
+        }
+    }
+
+    // remove the assigned request of a proof or failure, return none if the request is not
+    // assigned to the worker, which means it has been re-dispatched
+    pub fn complete(&mut self, msg: &GatewayMsg) -> Option<GatewayMsg> {
+        let worker = self.workers.get_mut(&msg.ip_addr())?;
+        let key = match msg {
+            GatewayMsg::Riscv(RiscvMsg::Response(res), task_id, _) => {
+                ChunkKey::Riscv(*task_id, res.chunk_index)
+            }
+            GatewayMsg::Combine(CombineMsg::Response(res), task_id, _) => {
+                ChunkKey::Combine(*task_id, res.chunk_index)
+            }
+            GatewayMsg::ChunkFailed(ChunkFailure::Riscv(chunk_index, _), task_id, _) => {
+                ChunkKey::Riscv(*task_id, *chunk_index)
+            }
+            GatewayMsg::ChunkFailed(ChunkFailure::Combine(chunk_index, _), task_id, _) => {
+                ChunkKey::Combine(*task_id, *chunk_index)
+            }
+            // the compress and embed proofs are generated right after the final combine proof
+            GatewayMsg::Compress(_, task_id, _) | GatewayMsg::Embed(_, _, task_id, _) => {
+                *worker.requests.iter().find_map(|(key, req)| match req {
+                    GatewayMsg::Combine(CombineMsg::Request(req), ..)
+                        if req.flag_complete && key.task_id() == *task_id =>
+                    {
+                        Some(key)
+                    }
+                    _ => None,
+                })?
+            }
+            _ => return None,
+        };
+
+        worker.leases.remove(&key);
+        worker.requests.remove(&key)
+    }
+
+    pub fn heartbeat(&mut self, ip_addr: IpAddr) {
+        self.workers.entry(ip_addr).or_default().last_heartbeat = Some(Instant::now());
+    }
+
+    // remove a worker, and return its outstanding requests
+    pub fn close(&mut self, ip_addr: &str) -> Vec<GatewayMsg> {
+        self.workers
+            .remove(ip_addr)
+            .map(|worker| worker.requests.into_values().collect())
+            .unwrap_or_default()
+    }
+
+    // remove the remote workers missing heartbeats, and return their outstanding requests
+    pub fn close_expired(&mut self) -> Vec<(IpAddr, Vec<GatewayMsg>)> {
+        let expired: Vec<_> = self
+            .workers
+            .iter()
+            .filter(|(_, worker)| {
+                worker
+                    .last_heartbeat
+                    .is_some_and(|last| last.elapsed() > self.heartbeat_timeout)
+            })
+            .map(|(ip_addr, _)| ip_addr.clone())
+            .collect();
+
+        expired
+            .into_iter()
+            .map(|ip_addr| {
+                let requests = self.close(&ip_addr);
+                (ip_addr, requests)
+            })
+            .collect()
+    }
+
+    // take back the pulled requests not acknowledged before their lease deadlines
+    pub fn expire_leases(&mut self) -> Vec<(IpAddr, GatewayMsg)> {
+        let now = Instant::now();
+        let mut expired = vec![];
+        for (ip_addr, worker) in self.workers.iter_mut() {
+            worker.leases.retain(|key, deadline| {
+                if *deadline > now {
+                    return true;
+                }
+                if let Some(msg) = worker.requests.remove(key) {
+                    expired.push((ip_addr.clone(), msg));
+                }
+                false
+            });
+        }
+
+        expired
+    }
+
+    // drop the requests of a completed, failed or cancelled task
+    pub fn remove_task(&mut self, task_id: TaskId) {
+        self.dispatched.retain(|key, _| key.task_id() != task_id);
+        self.workers.values_mut().for_each(|worker| {
+            worker.requests.retain(|key, _| key.task_id() != task_id);
+            worker.leases.retain(|key, _| key.task_id() != task_id);
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        proving::messages::combine::CombineRequest,
+        types::{EmbedSC, SC},
+    };
+    use pico_vm::{configs::config::StarkGenericConfig, machine::proof::MetaProof};
+    use std::thread::sleep;
+
+    const TIMEOUT: Duration = Duration::from_secs(60);
+
+    fn request(task_id: TaskId, chunk_index: usize, flag_complete: bool) -> GatewayMsg {
+        let req = CombineRequest::new(flag_complete, chunk_index, vec![]);
+        GatewayMsg::Combine(CombineMsg::Request(req), task_id, "".to_string())
+    }
+
+    fn failure(task_id: TaskId, chunk_index: usize, ip_addr: &str) -> GatewayMsg {
+        let failure = ChunkFailure::Combine(chunk_index, "failed".to_string());
+        GatewayMsg::ChunkFailed(failure, task_id, ip_addr.to_string())
+    }
+
+    fn empty_proof<C: StarkGenericConfig>() -> MetaProof<C> {
+        MetaProof::new(vec![].into(), vec![].into(), None)
+    }
+
+    // dispatch the requests and assign them to the worker
+    fn assign(assignments: &mut Assignments, requests: &[GatewayMsg], ip_addr: &str) {
+        for msg in requests {
+            assignments.dispatch(msg);
+            let key = ChunkKey::of_request(msg).unwrap();
+            assignments.assign(key, ip_addr.to_string());
+        }
+    }
+
+    // chunk keys and workers of the requests
+    fn keys(requests: &[GatewayMsg]) -> Vec<(ChunkKey, IpAddr)> {
+        let mut keys: Vec<_> = requests
+            .iter()
+            .map(|msg| (ChunkKey::of_request(msg).unwrap(), msg.ip_addr()))
+            .collect();
+        keys.sort_by_key(|(key, _)| format!("{key:?}"));
+        keys
+    }
+
+    #[test]
+    fn test_complete_assigned() {
+        let mut assignments = Assignments::new(TIMEOUT, TIMEOUT);
+        assign(&mut assignments, &[request(1, 0, false)], "prover-0");
+        assert!(assignments.dispatched.is_empty());
+
+        // the failure reported by another worker is ignored
+        assert!(assignments.complete(&failure(1, 0, "prover-1")).is_none());
+
+        let assigned = assignments.complete(&failure(1, 0, "prover-0")).unwrap();
+        assert_eq!(
+            keys(&[assigned]),
+            [(ChunkKey::Combine(1, 0), "prover-0".into())]
+        );
+        assert!(assignments.complete(&failure(1, 0, "prover-0")).is_none());
+    }
+
+    #[test]
+    fn test_complete_final_proofs() {
+        let mut assignments = Assignments::new(TIMEOUT, TIMEOUT);
+        let requests = [
+            request(1, 2, false),
+            request(1, 3, true),
+            request(2, 4, true),
+        ];
+        assign(&mut assignments, &requests, "prover-0");
+
+        // the final proofs complete the combine request with the complete flag of the task
+        let compress = GatewayMsg::Compress(empty_proof::<SC>(), 1, "prover-1".to_string());
+        assert!(assignments.complete(&compress).is_none());
+        let compress = GatewayMsg::Compress(empty_proof::<SC>(), 1, "prover-0".to_string());
+        let assigned = assignments.complete(&compress).unwrap();
+        assert_eq!(
+            keys(&[assigned]),
+            [(ChunkKey::Combine(1, 3), "prover-0".into())]
+        );
+        assert!(assignments.complete(&compress).is_none());
+
+        let embed = GatewayMsg::Embed(empty_proof::<EmbedSC>(), None, 2, "prover-0".to_string());
+        let assigned = assignments.complete(&embed).unwrap();
+        assert_eq!(
+            keys(&[assigned]),
+            [(ChunkKey::Combine(2, 4), "prover-0".into())]
+        );
+
+        // the combine request without the complete flag is still assigned
+        let requests = assignments.close("prover-0");
+        assert_eq!(
+            keys(&requests),
+            [(ChunkKey::Combine(1, 2), "prover-0".into())]
+        );
+    }
+
+    #[test]
+    fn test_expire_leases() {
+        let mut assignments = Assignments::new(TIMEOUT, Duration::ZERO);
+        for chunk_index in 0..3 {
+            assignments.dispatch(&request(1, chunk_index, false));
+        }
+
+        // a request is only leased once, and the acknowledged one is kept by the worker
+        assignments.lease(ChunkKey::Combine(1, 0), "worker-0".to_string());
+        assignments.lease(ChunkKey::Combine(1, 1), "worker-0".to_string());
+        assignments.lease(ChunkKey::Combine(1, 1), "worker-1".to_string());
+        assignments.ack(ChunkKey::Combine(1, 0), "worker-0");
+
+        let (ip_addrs, requests): (Vec<_>, Vec<_>) =
+            assignments.expire_leases().into_iter().unzip();
+        assert_eq!(ip_addrs, ["worker-0"]);
+        assert_eq!(
+            keys(&requests),
+            [(ChunkKey::Combine(1, 1), "worker-0".into())]
+        );
+        assert!(assignments.expire_leases().is_empty());
+
+        // the failure of the expired request is dropped since it has been taken back
+        assert!(assignments.complete(&failure(1, 1, "worker-0")).is_none());
+        assert!(assignments.complete(&failure(1, 0, "worker-0")).is_some());
+        assert_eq!(assignments.dispatched.len(), 1);
+    }
+
+    #[test]
+    fn test_close_expired() {
+        let mut assignments = Assignments::new(Duration::ZERO, TIMEOUT);
+        assignments.heartbeat("worker-0".to_string());
+        assign(&mut assignments, &[request(1, 0, false)], "worker-0");
+        assign(&mut assignments, &[request(1, 1, false)], "prover-0");
+        sleep(Duration::from_millis(1));
+
+        // the local prover never sends heartbeats, so it's not closed
+        let (ip_addrs, requests): (Vec<_>, Vec<_>) =
+            assignments.close_expired().into_iter().unzip();
+        assert_eq!(ip_addrs, ["worker-0"]);
+        assert_eq!(
+            keys(&requests.concat()),
+            [(ChunkKey::Combine(1, 0), "worker-0".into())]
+        );
+        assert!(assignments.close_expired().is_empty());
+        assert!(assignments.complete(&failure(1, 1, "prover-0")).is_some());
+    }
+
+    #[test]
+    fn test_close_and_redispatch() {
+        let mut assignments = Assignments::new(TIMEOUT, TIMEOUT);
+        assign(
+            &mut assignments,
+            &[request(1, 0, false), request(1, 1, false)],
+            "prover-0",
+        );
+        assert!(assignments.close("prover-1").is_empty());
+
+        let requests = assignments.close("prover-0");
+        assert_eq!(
+            keys(&requests),
+            [
+                (ChunkKey::Combine(1, 0), "prover-0".into()),
+                (ChunkKey::Combine(1, 1), "prover-0".into())
+            ]
+        );
+        assert!(assignments.close("prover-0").is_empty());
+
+        // the re-dispatched requests are taken by another worker
+        let requests: Vec<_> = requests
+            .into_iter()
+            .map(|msg| msg.with_ip_addr("".to_string()))
+            .collect();
+        assign(&mut assignments, &requests, "prover-1");
+        assert!(assignments.complete(&failure(1, 0, "prover-0")).is_none());
+        assert!(assignments.complete(&failure(1, 0, "prover-1")).is_some());
+        assert!(assignments.complete(&failure(1, 1, "prover-1")).is_some());
+    }
+
+    #[test]
+    fn test_remove_task() {
+        let mut assignments = Assignments::new(TIMEOUT, Duration::ZERO);
+        assignments.dispatch(&request(1, 0, false));
+        assignments.dispatch(&request(2, 0, false));
+        assign(&mut assignments, &[request(1, 1, false)], "prover-0");
+        assignments.dispatch(&request(1, 2, false));
+        assignments.lease(ChunkKey::Combine(1, 2), "worker-0".to_string());
+
+        assignments.remove_task(1);
+
+        // only the requests of the other task are kept
+        let dispatched: Vec<_> = assignments.dispatched.keys().copied().collect();
+        assert_eq!(dispatched, [ChunkKey::Combine(2, 0)]);
+        assert!(assignments.expire_leases().is_empty());
+        assert!(assignments.close("prover-0").is_empty());
+        assert!(assignments.close("worker-0").is_empty());
+    }
+}
@@ -1,52 +1,71 @@
 pub(crate) mod proof_tree;
 
 use crate::{
-    proving::messages::{
-        combine::{CombineMsg, CombineRequest, CombineResponse},
-        gateway::GatewayMsg,
-        riscv::{RiscvMsg, RiscvRequest, RiscvResponse},
+    TaskStatus,
+    proving::{
+        StarkProof,
+        messages::{
+            combine::{CombineMsg, CombineRequest, CombineResponse},
+            gateway::{ChunkFailure, GatewayMsg, TaskId},
+            riscv::{RiscvMsg, RiscvRequest, RiscvResponse},
+        },
     },
-    types::{EmbedSC, SC},
+    task_manager::{TaskEvent, TaskReporter},
+    types::SC,
 };
-use anyhow::Result;
+use anyhow::{Result, bail};
 use pico_vm::machine::proof::MetaProof;
 use proof_tree::ProofTree;
-use tracing::info;
+use std::collections::HashMap;
+use tracing::{info, warn};
 
 pub struct GatewayHandler {
     // identify if emulation is complete, it could be used to check if the leaves are complete in
     // proof tree
     emulator_complete: bool,
     proof_tree: ProofTree<MetaProof<SC>>,
-    // store the embed proof result
-    embed_proof: Option<MetaProof<EmbedSC>>,
+    // store the final compress or embed proof result
+    final_proof: Option<StarkProof>,
+    // report the task progress
+    reporter: TaskReporter,
+    // maximum retries of a failed chunk proof
+    max_retries: usize,
+    // retried times of the riscv-convert proofs by chunk index
+    riscv_retries: HashMap<usize, usize>,
+    // retried times of the combine proofs by chunk index
+    combine_retries: HashMap<usize, usize>,
 }
 
 impl GatewayHandler {
-    pub fn new() -> Self {
+    pub fn new(reporter: TaskReporter, max_retries: usize) -> Self {
         Self {
             emulator_complete: false,
             proof_tree: ProofTree::default(),
-            embed_proof: None,
+            final_proof: None,
+            reporter,
+            max_retries,
+            riscv_retries: HashMap::new(),
+            combine_retries: HashMap::new(),
         }
     }
 
     pub fn complete(&self) -> bool {
-        self.embed_proof.is_some()
+        self.final_proof.is_some()
     }
 
-    pub fn get_embed_proof(&self) -> Option<MetaProof<EmbedSC>> {
-        self.embed_proof.clone()
+    // the handler is removed once the task completes, so the proof is taken instead of cloned
+    pub fn take_final_proof(&mut self) -> Option<StarkProof> {
+        self.final_proof.take()
     }
 
-    pub fn set_embed_proof(&mut self, proof: MetaProof<EmbedSC>) {
-        self.embed_proof = Some(proof);
-        info!(
-            "[gateway] embed proof stored, size: {} bytes",
-            bincode::serialize(&self.embed_proof.as_ref().unwrap())
-                .unwrap()
-                .len()
-        );
+    pub fn set_final_proof(&mut self, proof: StarkProof) {
+        let size = match &proof {
+            StarkProof::Compress(proof) => bincode::serialized_size(proof),
+            StarkProof::Embed(proof, _) => bincode::serialized_size(proof),
+        }
+        .unwrap();
+        self.final_proof = Some(proof);
+        info!("[gateway] final proof stored, size: {size} bytes");
     }
 
     pub fn process_riscv_req(&mut self, msg: &GatewayMsg) -> Result<Option<GatewayMsg>> {
@@ -55,6 +74,7 @@ impl GatewayHandler {
                 RiscvMsg::Request(RiscvRequest { chunk_index, .. }) => {
                     // save the placeholder for the processing proof
                     self.proof_tree.init_node(*chunk_index);
+                    self.reporter.report(TaskEvent::ChunkEmulated(*chunk_index));
                 }
                 _ => {
                     panic!("unexpected message in process_riscv_req");
@@ -64,16 +84,81 @@ impl GatewayHandler {
         }
         Ok(None)
     }
+    // return the request to re-dispatch, or an error if the retries are exhausted, the assigned
+    // request kept by gateway is re-dispatched for a riscv failure if its record is not consumed
+    pub fn process_failure(
+        &mut self,
+        failure: ChunkFailure,
+        assigned: GatewayMsg,
+        task_id: TaskId,
+    ) -> Result<GatewayMsg> {
+        let (retries, chunk_index, stage, reason) = match &failure {
+            ChunkFailure::Riscv(chunk_index, reason) => {
+                (&mut self.riscv_retries, *chunk_index, "riscv", reason)
+            }
+            ChunkFailure::Combine(chunk_index, reason) => {
+                (&mut self.combine_retries, *chunk_index, "combine", reason)
+            }
+        };
+
+        // the record taken by a local prover is consumed, only the ones sent to remote workers
+        // are kept for retrying
+        if matches!(&assigned, GatewayMsg::Riscv(RiscvMsg::Request(req), ..) if req.record.is_taken())
+        {
+            bail!("{stage} proving of chunk-{chunk_index} failed: {reason}");
+        }
+
+        let retried = retries.entry(chunk_index).or_default();
+        if *retried >= self.max_retries {
+            bail!(
+                "{stage} proving of chunk-{chunk_index} failed after {} retries: {reason}",
+                self.max_retries,
+            );
+        }
+        *retried += 1;
+        warn!(
+            "[gateway] retry {stage} of task-{task_id} chunk-{chunk_index} ({}/{}): {reason}",
+            retried, self.max_retries,
+        );
+
+        let msg = match failure {
+            ChunkFailure::Riscv(..) => assigned.with_ip_addr("".to_string()),
+            ChunkFailure::Combine(chunk_index, _) => {
+                // rebuild the combine request from the sub-proofs kept in proof tree
+                let Some(proofs) = self.proof_tree.proofs_to_retry(chunk_index) else {
+                    bail!("no sub-proofs to retry combine proving of chunk-{chunk_index}");
+                };
+
+                GatewayMsg::Combine(
+                    CombineMsg::Request(CombineRequest {
+                        flag_complete: self.proof_tree.len() == 1, // TODO: check this
+                        chunk_index,
+                        proofs,
+                    }),
+                    task_id,
+                    "".to_string(),
+                )
+            }
+        };
+
+        Ok(msg)
+    }
+
     pub fn process(&mut self, msg: GatewayMsg) -> Result<Option<GatewayMsg>> {
+        let task_id = msg.task_id();
         let mut index_proofs_to_combine = None;
         match msg {
-            GatewayMsg::EmulatorComplete => self.emulator_complete = true,
+            GatewayMsg::EmulatorComplete(_) => {
+                self.emulator_complete = true;
+                self.reporter.report(TaskEvent::Status(TaskStatus::Proving));
+            }
             GatewayMsg::Riscv(msg, _, _) => match msg {
                 RiscvMsg::Request(RiscvRequest { chunk_index, .. }) => {
                     // save the placeholder for the processing proof
                     self.proof_tree.init_node(chunk_index);
                 }
                 RiscvMsg::Response(RiscvResponse { chunk_index, proof }) => {
+                    self.reporter.report(TaskEvent::ChunkProved(chunk_index));
                     index_proofs_to_combine = self
                         .proof_tree
                         .set_proof(chunk_index, proof)
@@ -85,14 +170,23 @@ impl GatewayHandler {
                 _,
                 _,
             ) => {
+                self.reporter.report(TaskEvent::ChunksCombined(
+                    proof.start_chunk,
+                    proof.end_chunk,
+                ));
                 index_proofs_to_combine = self
                     .proof_tree
                     .set_proof(chunk_index, proof)
                     .map(|proofs| (chunk_index, proofs));
             }
-            GatewayMsg::Embed(proof) => {
+            GatewayMsg::Compress(proof, _, _) => {
+                // Store the compress proof directly from worker prover
+                self.set_final_proof(StarkProof::Compress(proof));
+                info!("[gateway] received compress proof from worker prover");
+            }
+            GatewayMsg::Embed(proof, compress_proof, _, _) => {
                 // Store the embed proof directly from worker prover
-                self.set_embed_proof(proof);
+                self.set_final_proof(StarkProof::Embed(proof, compress_proof));
                 info!("[gateway] received embed proof from worker prover");
             }
             // Compress and embed phases are now handled directly in worker provers
@@ -100,7 +194,7 @@ impl GatewayHandler {
             _ => panic!("unsupported"),
         }
 
-        // Check if proving is completely done (embed completed)
+        // Check if proving is completely done (compress or embed completed)
         if self.complete() {
             info!("[gateway] proving complete");
 
@@ -110,6 +204,7 @@ impl GatewayHandler {
 
         if let Some((chunk_index, proofs)) = index_proofs_to_combine {
             assert_eq!(proofs.len(), 2);
+            let task_id = task_id.expect("proof message must have a task id");
 
             // return the combine message
             return Ok(Some(GatewayMsg::Combine(
@@ -118,7 +213,7 @@ impl GatewayHandler {
                     chunk_index,
                     proofs,
                 }),
-                chunk_index.to_string(),
+                task_id,
                 "".to_string(),
             )));
         }
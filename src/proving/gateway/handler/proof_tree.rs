@@ -131,6 +131,14 @@ impl<P> ProofTree<P> {
         tracing::info!("after init_node:\n{self}");
     }
 
+    // return the sub-proofs of an in-progress combine node for retrying
+    pub fn proofs_to_retry(&self, index: ProofIndex) -> Option<Vec<IndexedProof<P>>> {
+        match self.tree.get(&index)? {
+            ProofNode::InProgress(proofs) if !proofs.is_empty() => Some(proofs.clone()),
+            _ => None,
+        }
+    }
+
     // return two adjacent nodes for combine proving if any
     pub fn set_proof(
         &mut self,
@@ -1,30 +1,49 @@
 use crate::proving::{
-    messages::{combine::CombineMsg, gateway::GatewayMsg, riscv::RiscvMsg},
-    onchain::prove_embed_onchain,
+    TaskRegistry,
+    messages::{
+        combine::CombineMsg,
+        gateway::{GatewayMsg, TaskId},
+        riscv::RiscvMsg,
+    },
 };
-use crossbeam::channel::{Receiver, select_biased};
+use anyhow::anyhow;
+use assignment::Assignments;
+use crossbeam::channel::{Receiver, select_biased, tick};
 use handler::GatewayHandler;
 use log::debug;
 use pico_vm::thread::channel::DuplexUnboundedEndpoint;
-use std::sync::Arc;
+use std::{collections::HashMap, sync::Arc, time::Duration};
 use tokio::task::JoinHandle;
-use tracing::{error, info};
+use tracing::{error, info, warn};
 
+pub(crate) mod assignment;
 pub(crate) mod handler;
 
 pub type GatewayEndpoint = DuplexUnboundedEndpoint<GatewayMsg, GatewayMsg>;
 
+// interval of checking the heartbeats and leases of remote workers
+const HEARTBEAT_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
 pub fn run(
-    // exit the whole app directly if proving complete
     emulator_receiver: Arc<Receiver<GatewayMsg>>,
     grpc_endpoint: Arc<GatewayEndpoint>,
-    completion_sender: tokio::sync::oneshot::Sender<Vec<u8>>,
+    // registered tasks, the proving result is sent back by the completion sender of task
+    tasks: Arc<TaskRegistry>,
+    // maximum retries of a failed chunk proof before failing the task
+    max_chunk_retries: usize,
+    // a remote worker is dead if no heartbeat is received in this duration
+    heartbeat_timeout: Duration,
+    // a request pulled by a remote worker is re-dispatched if not acknowledged in this duration
+    lease_timeout: Duration,
 ) -> JoinHandle<()> {
     debug!("[coordinator] gateway init with proof callback");
 
     let thread_handle = tokio::task::spawn_blocking(move || {
-        let mut gateway_handler: GatewayHandler = GatewayHandler::new();
-        let mut completion_sender = Some(completion_sender);
+        // a proof tree for each task in proving
+        let mut gateway_handlers: HashMap<_, GatewayHandler> = HashMap::new();
+        // the requests taken by each worker, they're re-dispatched if the worker is dead
+        let mut assignments = Assignments::new(heartbeat_timeout, lease_timeout);
+        let heartbeat_ticker = tick(HEARTBEAT_CHECK_INTERVAL);
 
         loop {
             select_biased! {
@@ -33,19 +52,34 @@ pub fn run(
                         Ok(msg) => msg,
                         Err(_) => break, // Channel closed, exit gracefully
                     };
+                    let task_id = msg.task_id().expect("emulator message must have a task id");
+
+                    if let GatewayMsg::Cancel(_) = msg {
+                        debug!("[gateway] drop the proof tree of task-{task_id}");
+                        remove_task(&mut gateway_handlers, &mut assignments, task_id);
+                        continue;
+                    }
+
+                    // ignore the message if the task has been removed (failed)
+                    let Some(task) = tasks.get(&task_id).map(|t| t.value().clone()) else {
+                        debug!("[gateway] drop emulator message of unregistered task-{task_id}");
+                        remove_task(&mut gateway_handlers, &mut assignments, task_id);
+                        continue;
+                    };
+                    let gateway_handler = gateway_handlers
+                        .entry(task_id)
+                        .or_insert_with(|| GatewayHandler::new(task.reporter.clone(), max_chunk_retries));
+
                     match msg {
                         GatewayMsg::Riscv(RiscvMsg::Request(..), _, _) => {
                             let no_task = gateway_handler.process_riscv_req(&msg).unwrap();
                             assert!(no_task.is_none());
                             // send the task to grpc
-                            grpc_endpoint.send(msg).unwrap();
+                            dispatch(msg, &mut assignments, &grpc_endpoint);
                         }
-                        GatewayMsg::EmulatorComplete => {
-                            if let Some(exit_msg) = gateway_handler.process(msg.clone()).unwrap() {
-                                if matches!(exit_msg, GatewayMsg::Exit) {
-                                    break; // Exit the gateway loop
-                                }
-                            }
+                        GatewayMsg::EmulatorComplete(_) => {
+                            let no_task = gateway_handler.process(msg).unwrap();
+                            assert!(no_task.is_none());
                         }
                         _ => panic!("unsupported"),
                     }
@@ -55,54 +89,110 @@ pub fn run(
                         Ok(msg) => msg,
                         Err(_) => break, // Channel closed, exit gracefully
                     };
+
+                    // the assigned request of a proof or failure, it's none if the request has
+                    // been re-dispatched from a dead worker
+                    let assigned = assignments.complete(&msg);
+
                     match msg {
-                        GatewayMsg::Riscv(RiscvMsg::Response(..), _, _)
-                        | GatewayMsg::Combine(CombineMsg::Response(..), _, _)
-                        | GatewayMsg::Embed(..)
-                        | GatewayMsg::Exit => {
+                        GatewayMsg::Riscv(RiscvMsg::Response(..), task_id, _)
+                        | GatewayMsg::Combine(CombineMsg::Response(..), task_id, _)
+                        | GatewayMsg::Compress(_, task_id, _)
+                        | GatewayMsg::Embed(_, _, task_id, _) => {
+                            // ignore the proof if the task has been removed (failed)
+                            let Some(task) = tasks.get(&task_id).map(|t| t.value().clone()) else {
+                                debug!("[gateway] drop proof of unregistered task-{task_id}");
+                                remove_task(&mut gateway_handlers, &mut assignments, task_id);
+                                continue;
+                            };
+                            let Some(gateway_handler) = gateway_handlers.get_mut(&task_id) else {
+                                debug!("[gateway] drop proof of unknown task-{task_id}");
+                                continue;
+                            };
+                            if assigned.is_none() {
+                                debug!("[gateway] drop proof of re-dispatched task-{task_id} request");
+                                continue;
+                            }
+
                             // save the generated proof to the chunk_index slot in proof tree
-                            if let Some(msg) = gateway_handler.process(msg.clone()).unwrap() {
+                            if let Some(msg) = gateway_handler.process(msg).unwrap() {
                                 match msg {
                                     GatewayMsg::Exit => {
-                                        info!("[gateway] received Exit message, proving complete");
-                                        // Proving is complete. Generate on-chain proof and send via callback
-                                        if let Some(embed_proof) = gateway_handler.get_embed_proof() {
-                                            // Run on-chain dockerized phase to obtain final proof bytes
-                                            let proof_bytes = match prove_embed_onchain(embed_proof) {
-                                                Ok(bytes) => bytes,
-                                                Err(e) => {
-                                                    error!("[gateway] on-chain proof generation failed: {}", e);
-                                                    vec![]
-                                                }
-                                            };
-                                            let proof_size = proof_bytes.len();
-                                            info!("[gateway] sending final on-chain proof via callback, size: {} bytes", proof_size);
-
-                                            // Send proof via completion signal
-                                            if let Some(sender) = completion_sender.take() {
-                                                let _ = sender.send(proof_bytes);
-                                            }
-                                        } else {
-                                            error!("[gateway] Exit received but no embed proof available");
-                                            // Send empty proof to avoid hanging
-                                            if let Some(sender) = completion_sender.take() {
-                                                let _ = sender.send(vec![]);
-                                            }
-                                        }
-                                        break; // Exit the gateway loop
+                                        info!("[gateway] proving complete for task-{task_id}");
+                                        // Proving is complete, send the final proof via callback
+                                        let result = gateway_handler.take_final_proof().ok_or_else(|| {
+                                            error!("[gateway] Exit received but no final proof available");
+                                            anyhow!("no final proof available")
+                                        });
+                                        task.complete(result);
+                                        remove_task(&mut gateway_handlers, &mut assignments, task_id);
                                     }
                                     _ => {
                                         // send the new task (combine, compress, or embed) to grpc
-                                        grpc_endpoint.send(msg).unwrap();
+                                        dispatch(msg, &mut assignments, &grpc_endpoint);
                                     }
                                 }
                             }
                         }
+                        GatewayMsg::ChunkFailed(failure, task_id, _) => {
+                            let Some(task) = tasks.get(&task_id).map(|t| t.value().clone()) else {
+                                debug!("[gateway] drop failure of unregistered task-{task_id}");
+                                remove_task(&mut gateway_handlers, &mut assignments, task_id);
+                                continue;
+                            };
+                            let Some(gateway_handler) = gateway_handlers.get_mut(&task_id) else {
+                                debug!("[gateway] drop failure of unknown task-{task_id}");
+                                continue;
+                            };
+                            let Some(assigned) = assigned else {
+                                debug!("[gateway] drop failure of re-dispatched task-{task_id} request");
+                                continue;
+                            };
+
+                            // re-dispatch the failed request, or fail the task if out of retries
+                            match gateway_handler.process_failure(failure, assigned, task_id) {
+                                Ok(msg) => dispatch(msg, &mut assignments, &grpc_endpoint),
+                                Err(e) => {
+                                    error!("[gateway] proving failed for task-{task_id}: {e}");
+                                    task.complete(Err(e));
+                                    remove_task(&mut gateway_handlers, &mut assignments, task_id);
+                                }
+                            }
+                        }
+                        GatewayMsg::Assigned(key, ip_addr) => {
+                            // ignore the request of a removed task
+                            if gateway_handlers.contains_key(&key.task_id()) {
+                                assignments.assign(key, ip_addr);
+                            }
+                        }
+                        GatewayMsg::Leased(key, ip_addr) => {
+                            if gateway_handlers.contains_key(&key.task_id()) {
+                                assignments.lease(key, ip_addr);
+                            }
+                        }
+                        GatewayMsg::Acked(key, ip_addr) => assignments.ack(key, &ip_addr),
+                        GatewayMsg::Heartbeat(ip_addr) => assignments.heartbeat(ip_addr),
+                        GatewayMsg::Close(ip_addr) => {
+                            let requests = assignments.close(&ip_addr);
+                            warn!("[gateway] worker {ip_addr} closed with {} requests", requests.len());
+                            redispatch(requests, &tasks, &mut gateway_handlers, &mut assignments, &grpc_endpoint);
+                        }
                         // nothing to do here, this's used for single-node
                         GatewayMsg::RequestTask => (),
                         _ => panic!("unsupported"),
                     }
                 }
+                recv(heartbeat_ticker) -> _ => {
+                    for (ip_addr, requests) in assignments.close_expired() {
+                        warn!("[gateway] worker {ip_addr} missed heartbeats with {} requests", requests.len());
+                        redispatch(requests, &tasks, &mut gateway_handlers, &mut assignments, &grpc_endpoint);
+                    }
+                    for (ip_addr, msg) in assignments.expire_leases() {
+                        let task_id = msg.task_id().unwrap_or_default();
+                        warn!("[gateway] worker {ip_addr} did not acknowledge a request of task-{task_id}");
+                        redispatch(vec![msg], &tasks, &mut gateway_handlers, &mut assignments, &grpc_endpoint);
+                    }
+                }
             }
         }
     });
@@ -112,4 +202,48 @@ pub fn run(
     thread_handle
 }
 
-// on-chain proof generation handled in Exit branch above
+// drop the proof tree and the assigned requests of a task
+fn remove_task(
+    gateway_handlers: &mut HashMap<TaskId, GatewayHandler>,
+    assignments: &mut Assignments,
+    task_id: TaskId,
+) {
+    gateway_handlers.remove(&task_id);
+    assignments.remove_task(task_id);
+}
+
+// send a riscv or combine request to the workers, it's kept until a worker takes it
+fn dispatch(msg: GatewayMsg, assignments: &mut Assignments, grpc_endpoint: &GatewayEndpoint) {
+    assignments.dispatch(&msg);
+    grpc_endpoint.send(msg).unwrap();
+}
+
+// put the outstanding requests of a closed or dead worker back to the dispatch queue, the task
+// fails if a riscv record has been consumed by the closed local prover
+fn redispatch(
+    requests: Vec<GatewayMsg>,
+    tasks: &TaskRegistry,
+    gateway_handlers: &mut HashMap<TaskId, GatewayHandler>,
+    assignments: &mut Assignments,
+    grpc_endpoint: &GatewayEndpoint,
+) {
+    for msg in requests {
+        let Some(task_id) = msg
+            .task_id()
+            .filter(|task_id| gateway_handlers.contains_key(task_id))
+        else {
+            continue;
+        };
+
+        if matches!(&msg, GatewayMsg::Riscv(RiscvMsg::Request(req), ..) if req.record.is_taken()) {
+            error!("[gateway] riscv record of task-{task_id} is lost with the closed prover");
+            if let Some(task) = tasks.get(&task_id).map(|t| t.value().clone()) {
+                task.complete(Err(anyhow!("riscv record is lost with a closed prover")));
+            }
+            remove_task(gateway_handlers, assignments, task_id);
+            continue;
+        }
+
+        dispatch(msg.with_ip_addr("".to_string()), assignments, grpc_endpoint);
+    }
+}
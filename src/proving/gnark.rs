@@ -0,0 +1,246 @@
+use anyhow::Result;
+use reqwest::{Client, StatusCode, header::CONTENT_TYPE};
+use std::time::Duration;
+use thiserror::Error;
+use tokio::time::sleep;
+use tracing::warn;
+
+// API paths of the gnark server
+const READY_PATH: &str = "/ready";
+const PROVE_PATH: &str = "/prove";
+
+/// Settings of the gnark server which wraps the embed proofs to the on-chain proofs.
+#[derive(Clone, Debug)]
+pub struct GnarkConfig {
+    // base URL of the gnark server
+    pub url: String,
+    // base URL of the gnark PLONK server with its own keys, PLONK wrapping is disabled if not set
+    pub plonk_url: Option<String>,
+    pub request_timeout: Duration,
+    // maximum retries of a request if the server is unreachable or returns a server error
+    pub max_retries: usize,
+    pub retry_interval: Duration,
+    // directory of the gnark `vm_pk`, `vm_vk` and `vm_ccs`
+    pub download_path: String,
+    // start and monitor the gnark docker container from the service
+    pub manage_docker: bool,
+}
+
+#[derive(Debug, Error)]
+pub enum GnarkError {
+    #[error("failed to request gnark server: {0}")]
+    Request(#[from] reqwest::Error),
+
+    #[error("gnark server returned {0}: {1}")]
+    Status(StatusCode, String),
+}
+
+impl GnarkError {
+    // the request is retried if the server is unreachable or fails internally, a timed out
+    // proving request is not retried since it could be still in proving
+    fn retryable(&self) -> bool {
+        match self {
+            Self::Request(e) => e.is_connect(),
+            Self::Status(status, _) => status.is_server_error(),
+        }
+    }
+}
+
+/// HTTP client of the gnark server, it works with any reachable server, e.g. the sidecar
+/// container or a remote one.
+#[derive(Clone)]
+pub struct GnarkClient {
+    http: Client,
+    url: String,
+    max_retries: usize,
+    retry_interval: Duration,
+}
+
+impl GnarkClient {
+    pub fn new(url: &str, cfg: &GnarkConfig) -> Result<Self> {
+        let http = Client::builder().timeout(cfg.request_timeout).build()?;
+
+        Ok(Self {
+            http,
+            url: url.trim_end_matches('/').to_string(),
+            max_retries: cfg.max_retries,
+            retry_interval: cfg.retry_interval,
+        })
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// Check if the server is ready for proving.
+    pub async fn ready(&self) -> bool {
+        self.http
+            .get(format!("{}{READY_PATH}", self.url))
+            .send()
+            .await
+            .is_ok_and(|res| res.status().is_success())
+    }
+
+    /// Send the witness JSON for proving, it returns the proof text of the comma separated values.
+    pub async fn prove(&self, witness_json: &str) -> Result<String, GnarkError> {
+        let mut retries = 0;
+        loop {
+            match self.send_prove(witness_json).await {
+                Err(e) if e.retryable() && retries < self.max_retries => {
+                    retries += 1;
+                    warn!(
+                        "[gnark] proving request failed ({retries}/{}), retry in {:?}: {e}",
+                        self.max_retries, self.retry_interval,
+                    );
+                    sleep(self.retry_interval).await;
+                }
+                res => return res,
+            }
+        }
+    }
+
+    async fn send_prove(&self, witness_json: &str) -> Result<String, GnarkError> {
+        let res = self
+            .http
+            .post(format!("{}{PROVE_PATH}", self.url))
+            .header(CONTENT_TYPE, "application/json")
+            .body(witness_json.to_string())
+            .send()
+            .await?;
+
+        let status = res.status();
+        let text = res.text().await?;
+        if !status.is_success() {
+            return Err(GnarkError::Status(status, text));
+        }
+
+        Ok(text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    };
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::{TcpListener, TcpStream},
+    };
+
+    const PROOF: &str = "1,2,3";
+
+    // HTTP server replying the proving requests with the statuses in order, the last status is
+    // repeated once they're used up, it returns the URL and the counter of received requests
+    async fn mock_server(statuses: Vec<u16>) -> (String, Arc<AtomicUsize>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let requests = Arc::new(AtomicUsize::new(0));
+
+        let counter = requests.clone();
+        tokio::spawn(async move {
+            loop {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                read_request(&mut stream).await;
+                let i = counter.fetch_add(1, Ordering::SeqCst);
+                let status = statuses[i.min(statuses.len() - 1)];
+                let body = if status == 200 { PROOF } else { "error" };
+                let res = format!(
+                    "HTTP/1.1 {status} MOCK\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{body}",
+                    body.len(),
+                );
+                stream.write_all(res.as_bytes()).await.unwrap();
+                stream.shutdown().await.unwrap();
+            }
+        });
+
+        (url, requests)
+    }
+
+    // read the request headers and the body of the content length
+    async fn read_request(stream: &mut TcpStream) {
+        let mut buf = vec![];
+        let mut chunk = [0; 1024];
+        loop {
+            let n = stream.read(&mut chunk).await.unwrap();
+            if n == 0 {
+                return;
+            }
+            buf.extend_from_slice(&chunk[..n]);
+
+            let text = String::from_utf8_lossy(&buf);
+            let Some(header_end) = text.find("\r\n\r\n") else {
+                continue;
+            };
+            let content_length = text[..header_end]
+                .lines()
+                .find_map(|line| {
+                    let (name, value) = line.split_once(':')?;
+                    name.eq_ignore_ascii_case("content-length")
+                        .then(|| value.trim().parse::<usize>().ok())?
+                })
+                .unwrap_or(0);
+            if buf.len() >= header_end + 4 + content_length {
+                return;
+            }
+        }
+    }
+
+    fn client(url: &str, max_retries: usize) -> GnarkClient {
+        let cfg = GnarkConfig {
+            url: url.to_string(),
+            plonk_url: None,
+            request_timeout: Duration::from_secs(5),
+            max_retries,
+            retry_interval: Duration::from_millis(10),
+            download_path: String::new(),
+            manage_docker: false,
+        };
+
+        GnarkClient::new(url, &cfg).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_prove() {
+        let (url, requests) = mock_server(vec![200]).await;
+
+        let proof = client(&url, 3).prove("{}").await.unwrap();
+        assert_eq!(proof, PROOF);
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_retry_on_server_error() {
+        let (url, requests) = mock_server(vec![500, 503, 200]).await;
+
+        let proof = client(&url, 3).prove("{}").await.unwrap();
+        assert_eq!(proof, PROOF);
+        assert_eq!(requests.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_no_retry_on_client_error() {
+        let (url, requests) = mock_server(vec![400, 200]).await;
+
+        let res = client(&url, 3).prove("{}").await;
+        assert!(matches!(
+            res,
+            Err(GnarkError::Status(StatusCode::BAD_REQUEST, _))
+        ));
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_give_up_after_max_retries() {
+        let (url, requests) = mock_server(vec![500]).await;
+
+        let res = client(&url, 2).prove("{}").await;
+        assert!(matches!(
+            res,
+            Err(GnarkError::Status(StatusCode::INTERNAL_SERVER_ERROR, _))
+        ));
+        assert_eq!(requests.load(Ordering::SeqCst), 3);
+    }
+}
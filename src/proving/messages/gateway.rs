@@ -1,26 +1,81 @@
 use crate::{
     proving::messages::{combine::CombineMsg, riscv::RiscvMsg},
-    types::EmbedSC,
+    types::{EmbedSC, SC},
 };
 use pico_vm::machine::proof::MetaProof;
 
-type IpAddr = String;
-type TaskId = String;
+// ID of the worker (local prover or remote worker) which takes the request
+pub type IpAddr = String;
+
+// unique ID of a proving task registered in the prover pool
+pub type TaskId = u64;
+
+// chunk proving failed in worker with the error message
+#[derive(Clone)]
+pub enum ChunkFailure {
+    // riscv-convert proving failed at the chunk index, the request is kept by gateway
+    Riscv(usize, String),
+    // combine proving failed at the chunk index, the sub-proofs are kept in proof tree
+    Combine(usize, String),
+}
+
+// stage, task and chunk index of a riscv or combine request
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum ChunkKey {
+    Riscv(TaskId, usize),
+    Combine(TaskId, usize),
+}
+
+impl ChunkKey {
+    pub fn task_id(&self) -> TaskId {
+        match self {
+            Self::Riscv(task_id, _) | Self::Combine(task_id, _) => *task_id,
+        }
+    }
+
+    pub fn of_request(msg: &GatewayMsg) -> Option<Self> {
+        match msg {
+            GatewayMsg::Riscv(RiscvMsg::Request(req), task_id, _) => {
+                Some(Self::Riscv(*task_id, req.chunk_index))
+            }
+            GatewayMsg::Combine(CombineMsg::Request(req), task_id, _) => {
+                Some(Self::Combine(*task_id, req.chunk_index))
+            }
+            _ => None,
+        }
+    }
+}
 
 #[allow(clippy::large_enum_variant)]
 #[derive(Clone)]
 pub enum GatewayMsg {
     // identify the emulator complete
     // TODO: add block number for multiple block proving
-    EmulatorComplete,
+    EmulatorComplete(TaskId),
     // request task by worker
     RequestTask,
     // riscv
     Riscv(RiscvMsg, TaskId, IpAddr),
     // combine
     Combine(CombineMsg, TaskId, IpAddr),
-    // embed proof from direct execution
-    Embed(MetaProof<EmbedSC>),
+    // compress proof from direct execution, it's the final proof of the task wrapped to STARK
+    Compress(MetaProof<SC>, TaskId, IpAddr),
+    // embed proof from direct execution, with the compress proof if it's kept by the task
+    Embed(MetaProof<EmbedSC>, Option<MetaProof<SC>>, TaskId, IpAddr),
+    // chunk proving failure reported by worker
+    ChunkFailed(ChunkFailure, TaskId, IpAddr),
+    // riscv or combine request taken by the worker, the request is kept by gateway since it's
+    // dispatched, so only the chunk key is sent back
+    Assigned(ChunkKey, IpAddr),
+    // riscv or combine request pulled by a remote worker, it's re-dispatched if not acknowledged
+    // before the lease timeout
+    Leased(ChunkKey, IpAddr),
+    // pulled request received by the remote worker
+    Acked(ChunkKey, IpAddr),
+    // heartbeat of a remote worker
+    Heartbeat(IpAddr),
+    // drop the proof tree of a cancelled or failed task
+    Cancel(TaskId),
     // close a client by ip
     Close(IpAddr),
     // exit
@@ -30,11 +85,43 @@ pub enum GatewayMsg {
 impl GatewayMsg {
     pub fn ip_addr(&self) -> IpAddr {
         match self {
-            Self::EmulatorComplete | Self::RequestTask | Self::Exit | Self::Embed(_) => "",
+            Self::EmulatorComplete(_) | Self::RequestTask | Self::Exit | Self::Cancel(_) => "",
             Self::Riscv(_, _, ip_addr) => ip_addr,
             Self::Combine(_, _, ip_addr) => ip_addr,
+            Self::Compress(_, _, ip_addr) => ip_addr,
+            Self::Embed(_, _, _, ip_addr) => ip_addr,
+            Self::ChunkFailed(_, _, ip_addr) => ip_addr,
+            Self::Assigned(_, ip_addr) | Self::Leased(_, ip_addr) | Self::Acked(_, ip_addr) => {
+                ip_addr
+            }
+            Self::Heartbeat(ip_addr) => ip_addr,
             Self::Close(ip_addr) => ip_addr,
         }
         .to_string()
     }
+
+    // set the worker of a riscv or combine request
+    pub fn with_ip_addr(self, ip_addr: IpAddr) -> Self {
+        match self {
+            Self::Riscv(msg, task_id, _) => Self::Riscv(msg, task_id, ip_addr),
+            Self::Combine(msg, task_id, _) => Self::Combine(msg, task_id, ip_addr),
+            msg => msg,
+        }
+    }
+
+    pub fn task_id(&self) -> Option<TaskId> {
+        match self {
+            Self::EmulatorComplete(task_id)
+            | Self::Riscv(_, task_id, _)
+            | Self::Combine(_, task_id, _)
+            | Self::ChunkFailed(_, task_id, _)
+            | Self::Compress(_, task_id, _)
+            | Self::Embed(_, _, task_id, _)
+            | Self::Cancel(task_id) => Some(*task_id),
+            Self::Assigned(key, _) | Self::Leased(key, _) | Self::Acked(key, _) => {
+                Some(key.task_id())
+            }
+            Self::RequestTask | Self::Heartbeat(_) | Self::Close(_) | Self::Exit => None,
+        }
+    }
 }
@@ -0,0 +1,243 @@
+// conversions between the gateway messages and the proto messages of remote workers
+use crate::{
+    AckTaskRequest, ProofResult, ProofTask, TaskType, WorkerInfo, WrapType,
+    proving::messages::{
+        combine::{CombineMsg, CombineRequest, CombineResponse},
+        gateway::{ChunkFailure, ChunkKey, GatewayMsg, TaskId},
+        riscv::{RiscvMsg, RiscvRequest, RiscvResponse, SharedRecord},
+    },
+    proving_queue::ProvingTask,
+};
+use anyhow::{Result, anyhow, bail};
+use std::sync::Arc;
+
+impl WorkerInfo {
+    // unique ID of a remote worker, it's used as the ip address field of gateway messages
+    pub fn worker_id(&self) -> String {
+        format!("{}@{}", self.name, self.ip)
+    }
+}
+
+impl WrapType {
+    // stage of the final proof generated by the provers, the on-chain proofs are wrapped from the
+    // embed proof
+    pub fn final_stage(&self) -> TaskType {
+        match self {
+            Self::Stark => TaskType::Compress,
+            Self::Embed | Self::Groth16 | Self::Plonk => TaskType::Embed,
+        }
+    }
+}
+
+impl ProofTask {
+    // convert a riscv or combine request dispatched by gateway for the proving task
+    pub fn from_request(msg: &GatewayMsg, task: &ProvingTask) -> Result<Self> {
+        let app_id = task.key.app_id().to_string();
+        let final_stage = Some(task.wrap.final_stage().into());
+        let keep_compress_proof = Some(task.keep_intermediate_proofs);
+        let task = match msg {
+            GatewayMsg::Riscv(RiscvMsg::Request(req), task_id, _) => Self {
+                id: task_id.to_string(),
+                task_type: TaskType::Riscv.into(),
+                chunk_index: req.chunk_index as u64,
+                record: Some(req.record.serialize()?),
+                flag_complete: None,
+                proofs: vec![],
+                app_id,
+                final_stage,
+                keep_compress_proof,
+            },
+            GatewayMsg::Combine(CombineMsg::Request(req), task_id, _) => Self {
+                id: task_id.to_string(),
+                task_type: TaskType::Combine.into(),
+                chunk_index: req.chunk_index as u64,
+                record: None,
+                flag_complete: Some(req.flag_complete),
+                proofs: req
+                    .proofs
+                    .iter()
+                    .map(bincode::serialize)
+                    .collect::<Result<_, _>>()?,
+                app_id,
+                final_stage,
+                keep_compress_proof,
+            },
+            _ => bail!("unsupported task message"),
+        };
+
+        Ok(task)
+    }
+
+    // wrap of the task for the local provers, only the final stage matters to them
+    pub fn wrap(&self) -> WrapType {
+        match self.final_stage() {
+            TaskType::Compress => WrapType::Stark,
+            _ => WrapType::Embed,
+        }
+    }
+
+    // convert back to the gateway request for the local provers of a remote worker
+    pub fn into_request(self, ip_addr: String) -> Result<GatewayMsg> {
+        let task_id = parse_task_id(&self.id)?;
+        let chunk_index = self.chunk_index as usize;
+
+        let msg = match self.task_type() {
+            TaskType::Riscv => {
+                let record = self
+                    .record
+                    .ok_or_else(|| anyhow!("missing record of riscv task"))?;
+                let record = SharedRecord::new(bincode::deserialize(&record)?);
+                let req = RiscvRequest::new(chunk_index, Arc::new(record));
+                GatewayMsg::Riscv(RiscvMsg::Request(req), task_id, ip_addr)
+            }
+            TaskType::Combine => {
+                let proofs = self
+                    .proofs
+                    .iter()
+                    .map(|proof| bincode::deserialize(proof))
+                    .collect::<Result<_, _>>()?;
+                let req = CombineRequest::new(self.flag_complete(), chunk_index, proofs);
+                GatewayMsg::Combine(CombineMsg::Request(req), task_id, ip_addr)
+            }
+            task_type => bail!("unsupported task type {task_type:?}"),
+        };
+
+        Ok(msg)
+    }
+}
+
+impl AckTaskRequest {
+    pub fn new(worker: WorkerInfo, task: &ProofTask) -> Self {
+        Self {
+            worker: Some(worker),
+            id: task.id.clone(),
+            task_type: task.task_type,
+            chunk_index: task.chunk_index,
+        }
+    }
+
+    pub fn chunk_key(&self) -> Result<ChunkKey> {
+        let task_id = parse_task_id(&self.id)?;
+        let chunk_index = self.chunk_index as usize;
+
+        match self.task_type() {
+            TaskType::Riscv => Ok(ChunkKey::Riscv(task_id, chunk_index)),
+            TaskType::Combine => Ok(ChunkKey::Combine(task_id, chunk_index)),
+            task_type => bail!("unsupported task type {task_type:?}"),
+        }
+    }
+}
+
+impl ProofResult {
+    // convert a proof or failure generated by the local provers of a remote worker
+    pub fn from_response(msg: &GatewayMsg) -> Result<Self> {
+        let mut compress_proof = None;
+        let (task_type, chunk_index, proof, error, task_id) = match msg {
+            GatewayMsg::Riscv(RiscvMsg::Response(res), task_id, _) => (
+                TaskType::Riscv,
+                res.chunk_index,
+                bincode::serialize(&res.proof)?,
+                None,
+                task_id,
+            ),
+            GatewayMsg::Combine(CombineMsg::Response(res), task_id, _) => (
+                TaskType::Combine,
+                res.chunk_index,
+                bincode::serialize(&res.proof)?,
+                None,
+                task_id,
+            ),
+            // the final compress or embed proof is generated right after the final combine proof
+            GatewayMsg::Compress(proof, task_id, _) => (
+                TaskType::Compress,
+                0,
+                bincode::serialize(proof)?,
+                None,
+                task_id,
+            ),
+            GatewayMsg::Embed(proof, compress, task_id, _) => {
+                compress_proof = compress.as_ref().map(bincode::serialize).transpose()?;
+                (
+                    TaskType::Embed,
+                    0,
+                    bincode::serialize(proof)?,
+                    None,
+                    task_id,
+                )
+            }
+            GatewayMsg::ChunkFailed(ChunkFailure::Riscv(chunk_index, reason), task_id, _) => (
+                TaskType::Riscv,
+                *chunk_index,
+                vec![],
+                Some(reason.clone()),
+                task_id,
+            ),
+            GatewayMsg::ChunkFailed(ChunkFailure::Combine(chunk_index, reason), task_id, _) => (
+                TaskType::Combine,
+                *chunk_index,
+                vec![],
+                Some(reason.clone()),
+                task_id,
+            ),
+            _ => bail!("unsupported proof message"),
+        };
+
+        Ok(Self {
+            id: task_id.to_string(),
+            task_type: task_type.into(),
+            chunk_index: chunk_index as u64,
+            proof,
+            error,
+            compress_proof,
+        })
+    }
+
+    // convert back to the gateway response or failure
+    pub fn into_response(self, ip_addr: String) -> Result<GatewayMsg> {
+        let task_id = self.task_id()?;
+        let chunk_index = self.chunk_index as usize;
+        let task_type = self.task_type();
+        if let Some(reason) = self.error {
+            let failure = match task_type {
+                TaskType::Riscv => ChunkFailure::Riscv(chunk_index, reason),
+                TaskType::Combine => ChunkFailure::Combine(chunk_index, reason),
+                task_type => bail!("unsupported failure of {task_type:?} task"),
+            };
+            return Ok(GatewayMsg::ChunkFailed(failure, task_id, ip_addr));
+        }
+
+        let msg = match task_type {
+            TaskType::Riscv => {
+                let res = RiscvResponse::new(chunk_index, bincode::deserialize(&self.proof)?);
+                GatewayMsg::Riscv(RiscvMsg::Response(res), task_id, ip_addr)
+            }
+            TaskType::Combine => {
+                let res = CombineResponse::new(chunk_index, bincode::deserialize(&self.proof)?);
+                GatewayMsg::Combine(CombineMsg::Response(res), task_id, ip_addr)
+            }
+            TaskType::Compress => {
+                GatewayMsg::Compress(bincode::deserialize(&self.proof)?, task_id, ip_addr)
+            }
+            TaskType::Embed => GatewayMsg::Embed(
+                bincode::deserialize(&self.proof)?,
+                self.compress_proof
+                    .as_deref()
+                    .map(bincode::deserialize)
+                    .transpose()?,
+                task_id,
+                ip_addr,
+            ),
+            task_type => bail!("unsupported proof type {task_type:?}"),
+        };
+
+        Ok(msg)
+    }
+
+    pub fn task_id(&self) -> Result<TaskId> {
+        parse_task_id(&self.id)
+    }
+}
+
+fn parse_task_id(id: &str) -> Result<TaskId> {
+    id.parse().map_err(|e| anyhow!("invalid task id {id}: {e}"))
+}
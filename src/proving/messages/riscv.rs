@@ -1,6 +1,8 @@
 use crate::{proving::gateway::handler::proof_tree::IndexedProof, types::SC};
+use anyhow::{Result, anyhow};
 use derive_more::Constructor;
 use pico_vm::{emulator::riscv::record::EmulationRecord, machine::proof::MetaProof};
+use std::sync::{Arc, Mutex};
 
 // TODO: rename
 #[allow(clippy::large_enum_variant)]
@@ -14,7 +16,38 @@ pub enum RiscvMsg {
 pub struct RiscvRequest {
     // TODO: add identifier
     pub chunk_index: usize,
-    pub record: EmulationRecord,
+    // shared with the copy kept by gateway for re-dispatching
+    pub record: Arc<SharedRecord>,
+}
+
+/// Emulation record shared by a dispatched riscv request and its copy kept by gateway. A local
+/// prover takes the record out for proving, while a remote worker only serializes it, so the
+/// record is kept for re-dispatching until the remote worker returns the proof.
+pub struct SharedRecord(Mutex<Option<EmulationRecord>>);
+
+impl SharedRecord {
+    pub fn new(record: EmulationRecord) -> Self {
+        Self(Mutex::new(Some(record)))
+    }
+
+    // take the record out, return none if it has been taken by another prover
+    pub fn take(&self) -> Option<EmulationRecord> {
+        self.0.lock().unwrap().take()
+    }
+
+    pub fn is_taken(&self) -> bool {
+        self.0.lock().unwrap().is_none()
+    }
+
+    // serialize the record for a remote worker without taking it
+    pub fn serialize(&self) -> Result<Vec<u8>> {
+        let record = self.0.lock().unwrap();
+        let record = record
+            .as_ref()
+            .ok_or_else(|| anyhow!("riscv record has been taken by a local prover"))?;
+
+        Ok(bincode::serialize(record)?)
+    }
 }
 
 #[derive(Clone, Constructor)]
@@ -1,78 +1,332 @@
 use crate::{
-    proving::worker::prover::{Prover, ProverRunner},
-    proving_queue::ProvingTask,
+    ProofStage, TaskStatus, WrapType,
+    proving::{
+        messages::gateway::{GatewayMsg, TaskId},
+        onchain::{Groth16Vk, OnchainProver, PublicInputs},
+        worker::{
+            WorkerEndpoint,
+            prover::{Prover, ProverRunner, recursion_keys::RecursionKeys},
+        },
+    },
+    proving_queue::{CancelSignal, IntermediateProof, ProvingOutput, ProvingTask},
+    task_manager::{TaskEvent, TaskReporter},
+    types::{EmbedSC, SC},
 };
-use anyhow::Result;
-use futures::future::join_all;
-use pico_vm::thread::channel::{DuplexUnboundedChannel, SingleUnboundedChannel};
-use tracing::info;
+use anyhow::{Result, anyhow, bail};
+use crossbeam::channel::Sender;
+use dashmap::DashMap;
+use pico_vm::{
+    configs::config::StarkGenericConfig,
+    machine::{
+        keys::{BaseVerifyingKey, HashableKey},
+        proof::MetaProof,
+    },
+    thread::channel::{DuplexUnboundedChannel, SingleUnboundedChannel},
+};
+use serde::Serialize;
+use std::{
+    path::PathBuf,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::Duration,
+};
+use tokio::{sync::oneshot, task::JoinHandle};
+use tracing::{error, info};
 
 mod emulator;
 pub mod gateway;
+pub mod gnark;
 pub mod messages;
 pub mod onchain;
 pub mod worker;
 
-pub async fn prove_task(task: ProvingTask, prover_count: usize) -> Result<Vec<u8>> {
-    info!("[proving] starting prove_task for: {:?}", task.key);
+/// Final STARK proof of a task generated by the provers, it's the compressed proof if the task is
+/// wrapped to STARK, otherwise the embed proof with the compress proof if it's kept by the task.
+#[allow(clippy::large_enum_variant)]
+pub enum StarkProof {
+    Compress(MetaProof<SC>),
+    Embed(MetaProof<EmbedSC>, Option<MetaProof<SC>>),
+}
+
+type CompletionSender = oneshot::Sender<Result<StarkProof>>;
+
+/// Proving task registered in the prover pool, it's shared by the gateway and workers.
+pub struct PoolTask {
+    pub task: ProvingTask,
+    pub reporter: TaskReporter,
+    completion_sender: Mutex<Option<CompletionSender>>,
+}
+
+impl PoolTask {
+    fn new(task: ProvingTask, reporter: TaskReporter, completion_sender: CompletionSender) -> Self {
+        Self {
+            task,
+            reporter,
+            completion_sender: Mutex::new(Some(completion_sender)),
+        }
+    }
+
+    // send the proving result, it only takes effect for the first call
+    pub fn complete(&self, result: Result<StarkProof>) {
+        if let Some(sender) = self.completion_sender.lock().unwrap().take() {
+            let _ = sender.send(result);
+        }
+    }
+}
+
+// tasks in proving, a task is removed once it completes or fails
+pub type TaskRegistry = DashMap<TaskId, Arc<PoolTask>>;
+
+/// Long-lived gateway and provers shared by all proving tasks, the machines of provers are only
+/// built once when the pool starts.
+pub struct ProverPool {
+    tasks: Arc<TaskRegistry>,
+    emulator_sender: Arc<Sender<GatewayMsg>>,
+    // worker side of the gateway channel, it's shared by the local provers and remote workers
+    worker_endpoint: Arc<WorkerEndpoint>,
+    // wrapper of the embed proofs to the on-chain proofs
+    onchain: OnchainProver,
+    next_task_id: AtomicU64,
+    _handles: Vec<JoinHandle<()>>,
+}
+
+impl ProverPool {
+    pub fn new(
+        prover_count: usize,
+        max_chunk_retries: usize,
+        heartbeat_timeout: Duration,
+        lease_timeout: Duration,
+        recursion_keys_dir: Option<PathBuf>,
+        onchain: OnchainProver,
+    ) -> Self {
+        info!("[proving] prover pool init with {prover_count} provers");
 
-    // Create a completion signal with proof result
-    let (completion_sender, completion_receiver) = tokio::sync::oneshot::channel();
+        let tasks = Arc::new(TaskRegistry::default());
+        let recursion_keys = Arc::new(RecursionKeys::new(recursion_keys_dir));
+        let emulator_gateway_channel = SingleUnboundedChannel::default();
+        let gateway_worker_channel = DuplexUnboundedChannel::default();
 
-    let emulator_gateway_channel = SingleUnboundedChannel::default();
-    let gateway_worker_channel = DuplexUnboundedChannel::default();
+        let mut handles = vec![];
 
-    // start gateway with proof callback
-    let gateway_handle = gateway::run(
-        emulator_gateway_channel.receiver(),
-        gateway_worker_channel.endpoint1(),
-        completion_sender,
-    );
+        // start gateway
+        handles.push(gateway::run(
+            emulator_gateway_channel.receiver(),
+            gateway_worker_channel.endpoint1(),
+            tasks.clone(),
+            max_chunk_retries,
+            heartbeat_timeout,
+            lease_timeout,
+        ));
 
-    // start provers
-    let provers: Vec<_> = (0..prover_count)
-        .enumerate()
-        .map(|(i, _)| {
+        // start provers
+        handles.extend((0..prover_count).map(|i| {
             let prover_id = format!("prover-{i}");
             let worker_endpoint = gateway_worker_channel.endpoint2().clone_inner();
 
-            if task.use_gpu {
-                info!("[proving] creating CUDA prover: {}", prover_id);
-                let prover = Prover::new_cuda(prover_id, worker_endpoint, task.clone());
-                prover.run_cuda()
-            } else {
-                info!("[proving] creating CPU prover: {}", prover_id);
-                let prover = Prover::new(prover_id, worker_endpoint, task.clone());
-                prover.run()
-            }
-        })
-        .collect();
+            info!("[proving] creating CPU prover: {}", prover_id);
+            let prover = Prover::new(
+                prover_id.clone(),
+                worker_endpoint.clone(),
+                tasks.clone(),
+                recursion_keys.clone(),
+            );
+            let prover_handle = prover.run();
 
-    // start emulator
-    // We no longer need an emulator channel and sending start message
-    emulator::run(task, emulator_gateway_channel.sender());
+            // close the prover once its thread exits, the requests it holds are re-dispatched
+            tokio::spawn(async move {
+                if let Err(e) = prover_handle.await {
+                    error!("[proving] {prover_id} exited unexpectedly: {e}");
+                }
+                let _ = worker_endpoint.send(GatewayMsg::Close(prover_id));
+            })
+        }));
 
-    // Wait for proving to complete
-    info!("[proving] waiting for proving to complete");
+        info!("[proving] prover pool init end");
 
-    // Wait for completion signal from gateway and get the proof
-    let proof_bytes = completion_receiver.await?;
-    info!("[proving] received completion signal from gateway with proof");
+        Self {
+            tasks,
+            emulator_sender: emulator_gateway_channel.sender(),
+            worker_endpoint: gateway_worker_channel.endpoint2().clone_inner(),
+            onchain,
+            next_task_id: AtomicU64::new(0),
+            _handles: handles,
+        }
+    }
 
-    // Wait for all handles to complete (with timeout to avoid hanging)
-    let timeout = tokio::time::timeout(std::time::Duration::from_secs(5), join_all(provers)).await;
-    match timeout {
-        Ok(_) => info!("[proving] all provers completed"),
-        Err(_) => info!("[proving] provers completion timed out"),
+    pub fn tasks(&self) -> Arc<TaskRegistry> {
+        self.tasks.clone()
     }
 
-    // Wait for gateway to complete (with timeout)
-    let timeout = tokio::time::timeout(std::time::Duration::from_secs(5), gateway_handle).await;
-    match timeout {
-        Ok(_) => info!("[proving] gateway completed"),
-        Err(_) => info!("[proving] gateway completion timed out"),
+    pub fn worker_endpoint(&self) -> Arc<WorkerEndpoint> {
+        self.worker_endpoint.clone()
     }
 
-    info!("[proving] proving workflow completed successfully");
-    Ok(proof_bytes)
+    /// Generate the final STARK proof by the shared gateway and provers, it's returned with the
+    /// public values stream committed by the program.
+    pub async fn prove_stark(
+        &self,
+        task: ProvingTask,
+        reporter: TaskReporter,
+        mut cancel: CancelSignal,
+    ) -> Result<(StarkProof, Vec<u8>)> {
+        info!("[proving] starting prove_stark for: {:?}", task.key);
+
+        if task.use_gpu {
+            bail!("GPU proving is not supported by the prover pool");
+        }
+
+        // register the task for routing the chunk proofs to its proof tree
+        let task_id = self.next_task_id.fetch_add(1, Ordering::Relaxed);
+        let (completion_sender, completion_receiver) = oneshot::channel();
+        let pool_task = PoolTask::new(task.clone(), reporter.clone(), completion_sender);
+        self.tasks.insert(task_id, Arc::new(pool_task));
+
+        // start emulator
+        let emulator_sender = self.emulator_sender.clone();
+        let emulator_cancel = cancel.clone();
+        let emulator_handle = tokio::task::spawn_blocking(move || {
+            emulator::run(task_id, task, emulator_sender, emulator_cancel, reporter)
+        });
+
+        // Wait for proving to complete
+        info!("[proving] waiting for proving to complete");
+
+        // return the emulation error directly, otherwise wait for the completion signal from
+        // gateway, the sender is dropped if the gateway exits unexpectedly
+        let result = match emulator_handle.await {
+            Ok(Ok(pv_stream)) => tokio::select! {
+                res = completion_receiver => res
+                    .map_err(|_| anyhow!("gateway exited without a proving result"))
+                    .and_then(|res| res)
+                    .map(|proof| (proof, pv_stream)),
+                _ = cancel.cancelled() => Err(anyhow!("task cancelled")),
+            },
+            Ok(Err(e)) => Err(anyhow!("emulation failed: {e}")),
+            Err(e) => Err(anyhow!("emulator panicked: {e}")),
+        };
+        info!("[proving] received completion signal for task-{task_id}");
+
+        // the workers drop the remaining chunks of the unregistered task, and the gateway drops
+        // the proof tree if the task is not complete
+        self.tasks.remove(&task_id);
+        if result.is_err() {
+            let _ = self.emulator_sender.send(GatewayMsg::Cancel(task_id));
+        }
+
+        result
+    }
+
+    /// Generate the final STARK proof and wrap it as requested by the task, the groth16 and
+    /// plonk proofs are generated by gnark. It's returned with the public inputs for on-chain
+    /// verification.
+    pub async fn prove_task(
+        &self,
+        task: ProvingTask,
+        reporter: TaskReporter,
+        cancel: CancelSignal,
+    ) -> Result<ProvingOutput> {
+        let vk_digest = task.vk.hash_str_via_bn254();
+        let wrap = task.wrap;
+        let keep_intermediate_proofs = task.keep_intermediate_proofs;
+        let (stark_proof, pv_stream) = self
+            .prove_stark(task, reporter.clone(), cancel.clone())
+            .await?;
+        if cancel.is_cancelled() {
+            bail!("task cancelled");
+        }
+
+        // serialize the intermediate proofs before the embed proof is consumed by gnark
+        let mut intermediate_proofs = vec![];
+        if keep_intermediate_proofs {
+            match &stark_proof {
+                StarkProof::Compress(proof) => {
+                    intermediate_proofs.push(intermediate_proof(ProofStage::Compress, proof)?);
+                }
+                StarkProof::Embed(proof, compress_proof) => {
+                    if let Some(compress_proof) = compress_proof {
+                        intermediate_proofs
+                            .push(intermediate_proof(ProofStage::Compress, compress_proof)?);
+                    }
+                    intermediate_proofs.push(intermediate_proof(ProofStage::Embed, proof)?);
+                }
+            }
+        }
+
+        let inputs = PublicInputs::new(&vk_digest, &pv_stream)?;
+        let proof_bytes = match (wrap, stark_proof) {
+            (WrapType::Stark, StarkProof::Compress(proof)) => bincode::serialize(&proof)?,
+            (WrapType::Embed, StarkProof::Embed(proof, _)) => bincode::serialize(&proof)?,
+            (WrapType::Groth16 | WrapType::Plonk, StarkProof::Embed(proof, _)) => {
+                self.prove_onchain(proof, wrap, &inputs, reporter, cancel)
+                    .await?
+            }
+            (wrap, _) => bail!("unexpected final proof of {wrap:?} wrap"),
+        };
+
+        info!("[proving] proving workflow completed successfully");
+        Ok(ProvingOutput::new(
+            Arc::from(proof_bytes),
+            inputs.pv_digest.to_be_bytes_vec(),
+            Arc::from(pv_stream),
+            vk_digest,
+            wrap,
+            intermediate_proofs,
+        ))
+    }
+
+    // wrap the embed proof to the on-chain proof by gnark, only the groth16 proof is verified
+    // natively since the plonk keys are kept by its gnark server
+    async fn prove_onchain(
+        &self,
+        embed_proof: MetaProof<EmbedSC>,
+        wrap: WrapType,
+        inputs: &PublicInputs,
+        reporter: TaskReporter,
+        mut cancel: CancelSignal,
+    ) -> Result<Vec<u8>> {
+        // Run on-chain gnark phase to obtain final proof bytes, the gnark request is dropped if
+        // the task is cancelled
+        reporter.report(TaskEvent::Status(TaskStatus::Wrapping));
+        let proof_bytes = tokio::select! {
+            res = self.onchain.prove(embed_proof, wrap) => res,
+            _ = cancel.cancelled() => bail!("task cancelled"),
+        }
+        .map_err(|e| anyhow!("on-chain proof generation failed: {e}"))?;
+        if proof_bytes.is_empty() {
+            bail!("empty proof generated");
+        }
+
+        // check the proof returned by gnark before it's stored
+        if wrap == WrapType::Groth16 {
+            Groth16Vk::load(self.onchain.download_path())?
+                .verify(&proof_bytes, inputs)
+                .map_err(|e| anyhow!("on-chain proof verification failed: {e}"))?;
+            info!("[proving] on-chain proof verified");
+        }
+
+        Ok(proof_bytes)
+    }
+}
+
+// serialize a compress or embed proof with its verifying key for downloading
+fn intermediate_proof<C: StarkGenericConfig>(
+    stage: ProofStage,
+    proof: &MetaProof<C>,
+) -> Result<IntermediateProof>
+where
+    MetaProof<C>: Serialize,
+    BaseVerifyingKey<C>: Serialize,
+{
+    let vk = proof
+        .vks()
+        .first()
+        .ok_or_else(|| anyhow!("{stage:?} proof has no VKs"))?;
+
+    Ok(IntermediateProof::new(
+        stage,
+        bincode::serialize(proof)?,
+        bincode::serialize(vk)?,
+    ))
 }
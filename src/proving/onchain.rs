@@ -1,11 +1,21 @@
-use crate::types::{EmbedSC, SC, Val};
-use alloy_primitives::U256;
-use anyhow::{Result, anyhow};
+use crate::{
+    ProofFormat, WrapType,
+    cost_estimation::pv_digest,
+    proving::gnark::{GnarkClient, GnarkConfig},
+    types::{EmbedSC, SC, Val},
+};
+use alloy_primitives::{U256, keccak256};
+use anyhow::{Result, anyhow, bail};
+use ark_bn254::{Bn254, Fq, Fq2, Fr, G1Affine, G2Affine};
+use ark_ec::{
+    AffineRepr, CurveGroup,
+    pairing::Pairing,
+    short_weierstrass::{Affine, SWCurveConfig},
+};
+use ark_ff::{BigInteger, One, PrimeField};
 use pico_perf::common::{
     bench_field::BenchField,
-    gnark_utils::{
-        get_download_path, gnark_prover_running, recreate_gnark_prover, send_gnark_prove_task,
-    },
+    gnark_utils::{gnark_prover_running, recreate_gnark_prover},
 };
 use pico_vm::{
     instances::{
@@ -19,36 +29,119 @@ use pico_vm::{
     machine::{machine::MachineBehavior, proof::MetaProof},
     primitives::consts::RECURSION_NUM_PVS,
 };
-use std::{path::PathBuf, sync::OnceLock, thread, time::Duration};
-use tracing::info;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::{fs, path::PathBuf, sync::OnceLock, thread, time::Duration};
+use tracing::{info, warn};
+
+// field of the gnark circuit and downloads, the embed proofs are always of KoalaBear
+const GNARK_FIELD: BenchField = BenchField::KoalaBear;
+
+// interval of checking the managed gnark docker container
+const DOCKER_MONITOR_INTERVAL: Duration = Duration::from_secs(15);
+
+static ONCHAIN_DAEMON: OnceLock<()> = OnceLock::new();
+
+/// Wrapper of the embed proofs to the on-chain proofs by the gnark servers.
+pub struct OnchainProver {
+    cfg: GnarkConfig,
+    // groth16 server
+    client: GnarkClient,
+    // plonk server, it's never managed by the service
+    plonk_client: Option<GnarkClient>,
+}
+
+impl OnchainProver {
+    pub fn new(cfg: GnarkConfig) -> Result<Self> {
+        let client = GnarkClient::new(&cfg.url, &cfg)?;
+        let plonk_client = cfg
+            .plonk_url
+            .as_deref()
+            .map(|url| GnarkClient::new(url, &cfg))
+            .transpose()?;
+
+        Ok(Self {
+            cfg,
+            client,
+            plonk_client,
+        })
+    }
 
-/// Ensure the gnark docker prover is up, generate on-chain witness JSON from the embed proof,
-/// send it to the dockerized prover, and return the resulting on-chain proof bytes.
-pub fn prove_embed_onchain(embed_proof: MetaProof<EmbedSC>) -> Result<Vec<u8>> {
-    // 1) Ensure dockerized gnark prover is running (recreate if necessary)
-    let field = BenchField::KoalaBear;
-    let download_path = get_download_path(field);
-    ensure_gnark_downloads(field)?;
+    pub fn download_path(&self) -> &str {
+        &self.cfg.download_path
+    }
+
+    /// Check if the gnark server is ready for proving.
+    pub async fn ready(&self) -> bool {
+        self.client.ready().await
+    }
+
+    /// Generate the on-chain witness JSON from the embed proof, send it to the gnark server of
+    /// the wrap, and return the resulting on-chain proof bytes.
+    pub async fn prove(&self, embed_proof: MetaProof<EmbedSC>, wrap: WrapType) -> Result<Vec<u8>> {
+        let (client, value_count) = match wrap {
+            WrapType::Groth16 => (&self.client, Some(PROOF_VALUE_COUNT)),
+            WrapType::Plonk => (
+                self.plonk_client
+                    .as_ref()
+                    .ok_or_else(|| anyhow!("gnark PLONK server is not configured"))?,
+                None,
+            ),
+            wrap => bail!("{wrap:?} proof is not generated by gnark"),
+        };
+
+        // 1) Build the on-chain constraints and witness, serialize to gnark witness JSON string,
+        // the groth16 docker container is (re)created if it's managed by the service
+        let cfg = self.cfg.clone();
+        let gnark_witness_json = tokio::task::spawn_blocking(move || {
+            if cfg.manage_docker && wrap == WrapType::Groth16 {
+                ensure_gnark_container(&cfg.download_path)?;
+            }
+
+            build_onchain_witness_json(embed_proof)
+        })
+        .await??;
+        // std::fs::write("embed-witness.json", &gnark_witness_json).unwrap();
+
+        // 2) Send to gnark server for proving
+        info!("[onchain] sending witness to gnark server {}", client.url());
+        let proof_text = client.prove(&gnark_witness_json).await?;
+        info!("[onchain] received gnark proof: {proof_text}");
+
+        decode_gnark_proof_to_bytes(&proof_text, value_count)
+    }
+
+    /// Start a background daemon that monitors the gnark docker container and restarts it if
+    /// needed, it's only started if the container is managed by the service.
+    pub fn start_docker_daemon(&self) {
+        if !self.cfg.manage_docker || ONCHAIN_DAEMON.set(()).is_err() {
+            return;
+        }
+
+        let download_path = self.cfg.download_path.clone();
+        thread::spawn(move || {
+            loop {
+                if let Err(e) = ensure_gnark_container(&download_path) {
+                    warn!("[onchain] failed to ensure gnark container: {e}");
+                }
+                thread::sleep(DOCKER_MONITOR_INTERVAL);
+            }
+        });
+        info!("[onchain] docker monitor daemon started");
+    }
+}
+
+// ensure the gnark docker container is running, recreate it if necessary
+fn ensure_gnark_container(download_path: &str) -> Result<()> {
+    ensure_gnark_downloads(download_path)?;
 
     if !gnark_prover_running() {
         info!("[onchain] gnark prover not running, (re)creating docker container");
-        recreate_gnark_prover(field, &download_path)?;
+        recreate_gnark_prover(GNARK_FIELD, download_path)?;
         info!("[onchain] gnark prover is ready");
-    } else {
-        info!("[onchain] gnark prover already running");
     }
 
-    // 2) Build the on-chain constraints and witness, serialize to gnark witness JSON string
-    let gnark_witness_json = build_onchain_witness_json(embed_proof)?;
-    // std::fs::write("embed-witness.json", &gnark_witness_json).unwrap();
-
-    // 3) Send to gnark docker server for proving
-    info!("[onchain] sending witness to dockerized gnark prover");
-    let proof_text = send_gnark_prove_task(gnark_witness_json)?;
-    info!("[onchain] received gnark proof: {proof_text}");
-
-    let bytes = decode_gnark_proof_to_bytes(&proof_text);
-    Ok(bytes)
+    Ok(())
 }
 
 fn build_onchain_witness_json(embed_proof: MetaProof<EmbedSC>) -> Result<String> {
@@ -87,17 +180,17 @@ fn build_onchain_witness_json(embed_proof: MetaProof<EmbedSC>) -> Result<String>
     Ok(gnark_witness)
 }
 
-fn ensure_gnark_downloads(field: BenchField) -> Result<()> {
-    let dir = PathBuf::from(get_download_path(field));
+fn ensure_gnark_downloads(download_path: &str) -> Result<()> {
+    let dir = PathBuf::from(download_path);
     let missing_files: Vec<_> = ["vm_pk", "vm_vk", "vm_ccs"]
         .into_iter()
         .filter(|f| !dir.join(f).exists())
         .collect();
     if !missing_files.is_empty() {
-        panic!(
-            "[onchain] ERROR: Required gnark files are missing for {:?}. Missing files: {:?}. \
+        bail!(
+            "required gnark files are missing for {:?}. Missing files: {:?}. \
              Please ensure these files are present in the download path: {}",
-            field,
+            GNARK_FIELD,
             missing_files,
             dir.display()
         );
@@ -105,28 +198,81 @@ fn ensure_gnark_downloads(field: BenchField) -> Result<()> {
     Ok(())
 }
 
-static ONCHAIN_DAEMON: OnceLock<()> = OnceLock::new();
+// solidity verifier contract of the groth16 verifying key in the gnark downloads
+const GROTH16_VERIFIER_CONTRACT: &str = "fixtures/Groth16Verifier.sol";
 
-/// Start a background daemon that monitors the dockerized gnark prover and restarts it if needed.
-pub fn start_onchain_daemon() {
-    if ONCHAIN_DAEMON.set(()).is_ok() {
-        thread::spawn(|| {
-            let field = BenchField::KoalaBear;
-            let download_path = get_download_path(field);
-            let interval = Duration::from_secs(15);
-            loop {
-                let _ = ensure_gnark_downloads(field);
-                if !gnark_prover_running() {
-                    let _ = recreate_gnark_prover(field, &download_path);
-                }
-                thread::sleep(interval);
-            }
-        });
-        info!("[onchain] docker monitor daemon started");
+/// Identity of the gnark Groth16 verifier for deploying the on-chain verifier contract.
+pub struct Groth16Verifier {
+    // sha256 hex of the gnark verifying key
+    pub vk_hash: String,
+    pub contract: String,
+}
+
+pub fn groth16_verifier(download_path: &str) -> Result<Groth16Verifier> {
+    let vk = read_gnark_vk(download_path)?;
+    let contract = fs::read_to_string(GROTH16_VERIFIER_CONTRACT)
+        .map_err(|e| anyhow!("failed to read {GROTH16_VERIFIER_CONTRACT}: {e}"))?;
+
+    Ok(Groth16Verifier {
+        vk_hash: hex::encode(Sha256::digest(vk)),
+        contract,
+    })
+}
+
+// number of uint256 values in the on-chain proof, the groth16 points (A, B, C) followed by the vk
+// digest and pv digest as public inputs
+const PROOF_VALUE_COUNT: usize = 10;
+
+// function of the groth16 verifier contract for the uncompressed proof
+const VERIFY_PROOF_SIGNATURE: &str = "verifyProof(uint256[8],uint256[2])";
+
+/// Groth16 proof and public inputs in JSON, the points are in the EIP-197 encoding of the verifier
+/// contract, e.g. `b` is `[[x1, x0], [y1, y0]]`.
+#[derive(Serialize)]
+struct JsonProof {
+    a: [String; 2],
+    b: [[String; 2]; 2],
+    c: [String; 2],
+    inputs: [String; 2],
+}
+
+/// Convert the raw on-chain proof bytes to the requested format.
+pub fn format_proof(proof: &[u8], format: ProofFormat) -> Result<Vec<u8>> {
+    if proof.len() != PROOF_VALUE_COUNT * U256::BYTES {
+        bail!(
+            "on-chain proof must have {} bytes, got {}",
+            PROOF_VALUE_COUNT * U256::BYTES,
+            proof.len()
+        );
     }
+
+    let formatted = match format {
+        ProofFormat::Raw => proof.to_vec(),
+        // the static arrays are encoded in place, so the arguments are the raw proof bytes
+        ProofFormat::Calldata => {
+            let selector = &keccak256(VERIFY_PROOF_SIGNATURE)[..4];
+            [selector, proof].concat()
+        }
+        ProofFormat::Json => {
+            let v: Vec<_> = proof
+                .chunks(U256::BYTES)
+                .map(|value| format!("0x{}", hex::encode(value)))
+                .collect();
+            let json = JsonProof {
+                a: [v[0].clone(), v[1].clone()],
+                b: [[v[2].clone(), v[3].clone()], [v[4].clone(), v[5].clone()]],
+                c: [v[6].clone(), v[7].clone()],
+                inputs: [v[8].clone(), v[9].clone()],
+            };
+            serde_json::to_vec(&json)?
+        }
+    };
+
+    Ok(formatted)
 }
 
-fn decode_gnark_proof_to_bytes(proof_text: &str) -> Vec<u8> {
+// the value count is only checked for groth16, the plonk proof size depends on the circuit
+fn decode_gnark_proof_to_bytes(proof_text: &str, value_count: Option<usize>) -> Result<Vec<u8>> {
     // remove the prefix and suffix double quotes
     let proof_text = proof_text.trim();
     let proof_text = proof_text.strip_prefix('"').unwrap_or(proof_text);
@@ -134,24 +280,22 @@ fn decode_gnark_proof_to_bytes(proof_text: &str) -> Vec<u8> {
 
     // separate proof text by comma
     let values: Vec<&str> = proof_text.split(',').map(|s| s.trim()).collect();
-    assert_eq!(values.len(), 10, "gnark proof must have 10 values");
+    if let Some(value_count) = value_count.filter(|count| values.len() != *count) {
+        bail!(
+            "gnark proof must have {value_count} values, got {}",
+            values.len()
+        );
+    }
 
-    let bytes: Vec<_> = values
-        .iter()
-        .flat_map(|s| {
-            // convert the value to an uint256
-            let u256 = U256::from_str_radix(s.trim_start_matches("0x"), 16)
-                .expect("failed to convert a hex string to an uint256");
+    let mut bytes = Vec::with_capacity(values.len() * U256::BYTES);
+    for s in values {
+        // convert the value to an uint256
+        let u256 = U256::from_str_radix(s.trim_start_matches("0x"), 16)
+            .map_err(|e| anyhow!("invalid gnark proof value {s}: {e}"))?;
 
-            // convert the uint256 to bytes of big-endian
-            u256.to_be_bytes_vec()
-        })
-        .collect();
-    assert_eq!(
-        bytes.len(),
-        320,
-        "ten uint256 could only be converted to 320 bytes",
-    );
+        // convert the uint256 to bytes of big-endian
+        bytes.extend(u256.to_be_bytes::<{ U256::BYTES }>());
+    }
 
     // enable for debugging
     // {
@@ -175,5 +319,536 @@ fn decode_gnark_proof_to_bytes(proof_text: &str) -> Vec<u8> {
     //     );
     // }
 
-    bytes
+    Ok(bytes)
+}
+
+// number of public inputs of the on-chain circuit
+const PUBLIC_INPUT_COUNT: usize = 2;
+
+// flags of the gnark point encoding in the 2 most significant bits
+const GNARK_FLAG_MASK: u8 = 0b1100_0000;
+const GNARK_UNCOMPRESSED: u8 = 0b0000_0000;
+const GNARK_COMPRESSED_INFINITY: u8 = 0b0100_0000;
+const GNARK_COMPRESSED_LARGEST: u8 = 0b1100_0000;
+
+static GROTH16_VK: OnceLock<Groth16Vk> = OnceLock::new();
+
+/// Public inputs of the on-chain proof.
+pub struct PublicInputs {
+    pub vk_digest: U256,
+    pub pv_digest: U256,
+}
+
+impl PublicInputs {
+    // the vk digest is the hex string of `hash_str_via_bn254`, the pv digest is computed from the
+    // committed public values stream
+    pub fn new(vk_digest: &str, pv_stream: &[u8]) -> Result<Self> {
+        let vk_digest = U256::from_str_radix(vk_digest.trim_start_matches("0x"), 16)
+            .map_err(|e| anyhow!("invalid vk digest {vk_digest}: {e}"))?;
+
+        Ok(Self {
+            vk_digest,
+            pv_digest: pv_digest(pv_stream),
+        })
+    }
+}
+
+/// Groth16 verifying key of the gnark on-chain circuit, it's loaded from `vm_vk` of the gnark
+/// downloads.
+pub struct Groth16Vk {
+    alpha: G1Affine,
+    beta: G2Affine,
+    gamma: G2Affine,
+    delta: G2Affine,
+    // bases of the public inputs, the first one is the constant term
+    k: Vec<G1Affine>,
+}
+
+impl Groth16Vk {
+    // the key is only loaded once, it's retried by the next call if failed
+    pub fn load(download_path: &str) -> Result<&'static Self> {
+        if let Some(vk) = GROTH16_VK.get() {
+            return Ok(vk);
+        }
+
+        let vk = Self::from_gnark_bytes(&read_gnark_vk(download_path)?)
+            .map_err(|e| anyhow!("failed to parse gnark vk: {e}"))?;
+
+        Ok(GROTH16_VK.get_or_init(|| vk))
+    }
+
+    // the verifying key written by gnark, the points could be either compressed or raw
+    fn from_gnark_bytes(bytes: &[u8]) -> Result<Self> {
+        let mut reader = GnarkReader(bytes);
+        let alpha = reader.read_g1()?;
+        let _beta_g1 = reader.read_g1()?;
+        let beta = reader.read_g2()?;
+        let gamma = reader.read_g2()?;
+        let _delta_g1 = reader.read_g1()?;
+        let delta = reader.read_g2()?;
+        let k = (0..reader.read_u32()?)
+            .map(|_| reader.read_g1())
+            .collect::<Result<Vec<_>>>()?;
+        if k.len() != PUBLIC_INPUT_COUNT + 1 {
+            bail!(
+                "gnark vk must have {} public input bases, got {}",
+                PUBLIC_INPUT_COUNT + 1,
+                k.len()
+            );
+        }
+
+        Ok(Self {
+            alpha,
+            beta,
+            gamma,
+            delta,
+            k,
+        })
+    }
+
+    /// Verify the raw on-chain proof, the public inputs in the proof must be the expected ones.
+    pub fn verify(&self, proof: &[u8], inputs: &PublicInputs) -> Result<()> {
+        if proof.len() != PROOF_VALUE_COUNT * U256::BYTES {
+            bail!(
+                "on-chain proof must have {} bytes, got {}",
+                PROOF_VALUE_COUNT * U256::BYTES,
+                proof.len()
+            );
+        }
+
+        // the points are in the EIP-197 encoding as the gnark raw encoding
+        let (points, proof_inputs) = proof.split_at(8 * U256::BYTES);
+        let expected_inputs = [inputs.vk_digest, inputs.pv_digest];
+        for (value, expected) in proof_inputs.chunks(U256::BYTES).zip(expected_inputs) {
+            let value = U256::from_be_slice(value);
+            if value != expected {
+                bail!("public input mismatched, expected {expected:#x}, got {value:#x}");
+            }
+        }
+        let (a, b, c) = (
+            g1_from_raw(&points[..64])?,
+            g2_from_raw(&points[64..192])?,
+            g1_from_raw(&points[192..])?,
+        );
+
+        // linear combination of the public inputs
+        let mut l = self.k[0].into_group();
+        for (input, base) in expected_inputs.iter().zip(&self.k[1..]) {
+            l += *base * fr_from_u256(*input)?;
+        }
+
+        // e(A, B) * e(L, -gamma) * e(C, -delta) * e(alpha, -beta) == 1
+        let pairing = Bn254::multi_pairing(
+            [a, l.into_affine(), c, self.alpha],
+            [b, -self.gamma, -self.delta, -self.beta],
+        );
+        if !pairing.0.is_one() {
+            bail!("invalid groth16 proof");
+        }
+
+        Ok(())
+    }
+}
+
+fn read_gnark_vk(download_path: &str) -> Result<Vec<u8>> {
+    let vk_path = PathBuf::from(download_path).join("vm_vk");
+
+    fs::read(&vk_path).map_err(|e| anyhow!("failed to read gnark vk {}: {e}", vk_path.display()))
+}
+
+// reader of the gnark binary encoding, the values are in big-endian
+struct GnarkReader<'a>(&'a [u8]);
+
+impl GnarkReader<'_> {
+    fn take(&mut self, len: usize) -> Result<&[u8]> {
+        if self.0.len() < len {
+            bail!("unexpected end of gnark encoding");
+        }
+        let (bytes, remaining) = self.0.split_at(len);
+        self.0 = remaining;
+
+        Ok(bytes)
+    }
+
+    fn flag(&self) -> Result<u8> {
+        self.0
+            .first()
+            .map(|b| b & GNARK_FLAG_MASK)
+            .ok_or_else(|| anyhow!("unexpected end of gnark encoding"))
+    }
+
+    fn read_u32(&mut self) -> Result<u32> {
+        Ok(u32::from_be_bytes(self.take(4)?.try_into()?))
+    }
+
+    fn read_g1(&mut self) -> Result<G1Affine> {
+        let flag = self.flag()?;
+        match flag {
+            GNARK_UNCOMPRESSED => g1_from_raw(self.take(64)?),
+            GNARK_COMPRESSED_INFINITY => {
+                self.take(32)?;
+                Ok(G1Affine::zero())
+            }
+            _ => {
+                let x = fq_from_compressed(self.take(32)?)?;
+                let point =
+                    G1Affine::get_point_from_x_unchecked(x, flag == GNARK_COMPRESSED_LARGEST)
+                        .ok_or_else(|| anyhow!("invalid compressed G1 point"))?;
+                checked_point(point)
+            }
+        }
+    }
+
+    fn read_g2(&mut self) -> Result<G2Affine> {
+        let flag = self.flag()?;
+        match flag {
+            GNARK_UNCOMPRESSED => g2_from_raw(self.take(128)?),
+            GNARK_COMPRESSED_INFINITY => {
+                self.take(64)?;
+                Ok(G2Affine::zero())
+            }
+            _ => {
+                let bytes = self.take(64)?;
+                let x = Fq2::new(fq(&bytes[32..])?, fq_from_compressed(&bytes[..32])?);
+                let point =
+                    G2Affine::get_point_from_x_unchecked(x, flag == GNARK_COMPRESSED_LARGEST)
+                        .ok_or_else(|| anyhow!("invalid compressed G2 point"))?;
+                checked_point(point)
+            }
+        }
+    }
+}
+
+// uncompressed G1 point of x and y, all zeros for the point at infinity
+fn g1_from_raw(bytes: &[u8]) -> Result<G1Affine> {
+    if bytes.iter().all(|b| *b == 0) {
+        return Ok(G1Affine::zero());
+    }
+
+    checked_point(G1Affine::new_unchecked(
+        fq(&bytes[..32])?,
+        fq(&bytes[32..64])?,
+    ))
+}
+
+// uncompressed G2 point of x.c1, x.c0, y.c1 and y.c0, all zeros for the point at infinity
+fn g2_from_raw(bytes: &[u8]) -> Result<G2Affine> {
+    if bytes.iter().all(|b| *b == 0) {
+        return Ok(G2Affine::zero());
+    }
+
+    let x = Fq2::new(fq(&bytes[32..64])?, fq(&bytes[..32])?);
+    let y = Fq2::new(fq(&bytes[96..128])?, fq(&bytes[64..96])?);
+    checked_point(G2Affine::new_unchecked(x, y))
+}
+
+fn checked_point<P: SWCurveConfig>(point: Affine<P>) -> Result<Affine<P>> {
+    if !point.is_on_curve() || !point.is_in_correct_subgroup_assuming_on_curve() {
+        bail!("point is not on the curve or not in the subgroup");
+    }
+
+    Ok(point)
+}
+
+fn fq_from_compressed(bytes: &[u8]) -> Result<Fq> {
+    let mut bytes = bytes.to_vec();
+    bytes[0] &= !GNARK_FLAG_MASK;
+
+    fq(&bytes)
+}
+
+// the field elements must be reduced as required by the verifier contract
+fn fq(bytes: &[u8]) -> Result<Fq> {
+    let value = Fq::from_be_bytes_mod_order(bytes);
+    if value.into_bigint().to_bytes_be() != bytes {
+        bail!("base field element is not reduced");
+    }
+
+    Ok(value)
+}
+
+fn fr_from_u256(value: U256) -> Result<Fr> {
+    let bytes = value.to_be_bytes::<{ U256::BYTES }>();
+    let fr = Fr::from_be_bytes_mod_order(&bytes);
+    if fr.into_bigint().to_bytes_be() != bytes {
+        bail!("public input {value:#x} is not in the scalar field");
+    }
+
+    Ok(fr)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{Value, json};
+
+    // groth16 vk of a circuit with 2 public inputs in the gnark encoding, and its raw on-chain proof
+    // of the public inputs below
+    const VK: &[u8] = include_bytes!("../../fixtures/groth16-vk.bin");
+    const COMPRESSED_VK: &[u8] = include_bytes!("../../fixtures/groth16-vk-compressed.bin");
+    const PROOF: &[u8] = include_bytes!("../../fixtures/groth16-proof.bin");
+    const VK_DIGEST: &str = "0x1a2b3c4d5e6f708192a3b4c5d6e7f8091a2b3c4d5e6f708192a3b4c5d6e7f809";
+    const PV_STREAM: &[u8] = b"pico groth16 fixture public values";
+
+    // offset of the public input bases in the vk, after the points of alpha, beta and delta
+    const VK_BASES_OFFSET: usize = 576;
+
+    fn inputs() -> PublicInputs {
+        PublicInputs::new(VK_DIGEST, PV_STREAM).unwrap()
+    }
+
+    fn word(proof: &[u8], index: usize) -> String {
+        format!("0x{}", hex::encode(&proof[index * 32..(index + 1) * 32]))
+    }
+
+    fn set_word(proof: &mut [u8], index: usize, value: U256) {
+        proof[index * 32..(index + 1) * 32].copy_from_slice(&value.to_be_bytes::<32>());
+    }
+
+    // add the field modulus to an uint256 value, the result is the same element but not reduced
+    fn unreduced<F: PrimeField>(bytes: &[u8]) -> U256 {
+        U256::from_be_slice(bytes) + U256::from_be_slice(&F::MODULUS.to_bytes_be())
+    }
+
+    fn assert_err<T>(res: Result<T>, msg: &str) {
+        let e = res.err().expect("unexpected success").to_string();
+        assert!(e.contains(msg), "unexpected error: {e}");
+    }
+
+    #[test]
+    fn test_verify_fixture() {
+        let vk = Groth16Vk::from_gnark_bytes(VK).unwrap();
+        assert_eq!(vk.k.len(), PUBLIC_INPUT_COUNT + 1);
+        vk.verify(PROOF, &inputs()).unwrap();
+    }
+
+    #[test]
+    fn test_compressed_vk() {
+        let vk = Groth16Vk::from_gnark_bytes(VK).unwrap();
+        let compressed = Groth16Vk::from_gnark_bytes(COMPRESSED_VK).unwrap();
+        assert_eq!(compressed.alpha, vk.alpha);
+        assert_eq!(compressed.beta, vk.beta);
+        assert_eq!(compressed.gamma, vk.gamma);
+        assert_eq!(compressed.delta, vk.delta);
+        assert_eq!(compressed.k, vk.k);
+        compressed.verify(PROOF, &inputs()).unwrap();
+    }
+
+    #[test]
+    fn test_vk_encoding_errors() {
+        assert_err(
+            Groth16Vk::from_gnark_bytes(&VK[..VK_BASES_OFFSET + 4 + 64]),
+            "unexpected end of gnark encoding",
+        );
+
+        // one public input base is missing
+        let mut vk = VK.to_vec();
+        vk[VK_BASES_OFFSET..VK_BASES_OFFSET + 4].copy_from_slice(&2u32.to_be_bytes());
+        assert_err(
+            Groth16Vk::from_gnark_bytes(&vk),
+            "gnark vk must have 3 public input bases, got 2",
+        );
+
+        // the y coordinate of alpha is changed
+        let mut vk = VK.to_vec();
+        vk[63] ^= 1;
+        assert_err(Groth16Vk::from_gnark_bytes(&vk), "not on the curve");
+    }
+
+    #[test]
+    fn test_points_at_infinity() {
+        assert!(g1_from_raw(&[0; 64]).unwrap().is_zero());
+        assert!(g2_from_raw(&[0; 128]).unwrap().is_zero());
+
+        let mut bytes = [0; 96];
+        bytes[0] = GNARK_COMPRESSED_INFINITY;
+        bytes[32] = GNARK_COMPRESSED_INFINITY;
+        let mut reader = GnarkReader(&bytes);
+        assert!(reader.read_g1().unwrap().is_zero());
+        assert!(reader.read_g2().unwrap().is_zero());
+        assert_err(reader.read_g1(), "unexpected end of gnark encoding");
+    }
+
+    #[test]
+    fn test_tampered_proof() {
+        let vk = Groth16Vk::from_gnark_bytes(VK).unwrap();
+
+        // A is replaced by its negation, which is still a valid point
+        let mut proof = PROOF.to_vec();
+        let a = g1_from_raw(&PROOF[..64]).unwrap();
+        let y = U256::from_be_slice(&(-a).y.into_bigint().to_bytes_be());
+        set_word(&mut proof, 1, y);
+        assert_err(vk.verify(&proof, &inputs()), "invalid groth16 proof");
+
+        // A and C are swapped
+        let mut proof = PROOF.to_vec();
+        proof[..64].copy_from_slice(&PROOF[192..256]);
+        proof[192..256].copy_from_slice(&PROOF[..64]);
+        assert_err(vk.verify(&proof, &inputs()), "invalid groth16 proof");
+
+        // the coordinates of B are not on the curve
+        let mut proof = PROOF.to_vec();
+        proof[191] ^= 1;
+        assert_err(vk.verify(&proof, &inputs()), "not on the curve");
+
+        assert_err(
+            vk.verify(&PROOF[..PROOF.len() - 1], &inputs()),
+            "on-chain proof must have 320 bytes, got 319",
+        );
+    }
+
+    #[test]
+    fn test_mismatched_inputs() {
+        let vk = Groth16Vk::from_gnark_bytes(VK).unwrap();
+        let other_vk_digest = format!("0x{}", hex::encode(U256::from(1).to_be_bytes::<32>()));
+
+        let inputs = PublicInputs::new(&other_vk_digest, PV_STREAM).unwrap();
+        assert_err(vk.verify(PROOF, &inputs), "public input mismatched");
+        let inputs = PublicInputs::new(VK_DIGEST, b"other public values").unwrap();
+        assert_err(vk.verify(PROOF, &inputs), "public input mismatched");
+
+        // the public inputs in the proof are replaced, but the points are for the original ones
+        let inputs = PublicInputs::new(&other_vk_digest, b"other public values").unwrap();
+        let mut proof = PROOF.to_vec();
+        set_word(&mut proof, 8, inputs.vk_digest);
+        set_word(&mut proof, 9, inputs.pv_digest);
+        assert_err(vk.verify(&proof, &inputs), "invalid groth16 proof");
+    }
+
+    #[test]
+    fn test_non_reduced_elements() {
+        let vk = Groth16Vk::from_gnark_bytes(VK).unwrap();
+        let modulus = Fq::MODULUS.to_bytes_be();
+        assert_err(fq(&modulus), "base field element is not reduced");
+        assert!(fq(&(U256::from_be_slice(&modulus) - U256::from(1)).to_be_bytes::<32>()).is_ok());
+
+        // the x coordinate of A in the proof
+        let mut proof = PROOF.to_vec();
+        set_word(&mut proof, 0, unreduced::<Fq>(&PROOF[..32]));
+        assert_err(
+            vk.verify(&proof, &inputs()),
+            "base field element is not reduced",
+        );
+
+        // the y coordinate of alpha in the vk, the flags are only in the x coordinate
+        let mut vk_bytes = VK.to_vec();
+        set_word(&mut vk_bytes, 1, unreduced::<Fq>(&VK[32..64]));
+        assert_err(
+            Groth16Vk::from_gnark_bytes(&vk_bytes),
+            "base field element is not reduced",
+        );
+
+        // the public input is the vk digest plus the scalar field modulus
+        let vk_digest = unreduced::<Fr>(&PROOF[256..288]);
+        let inputs = PublicInputs::new(&format!("{vk_digest:#x}"), PV_STREAM).unwrap();
+        let mut proof = PROOF.to_vec();
+        set_word(&mut proof, 8, vk_digest);
+        assert_err(vk.verify(&proof, &inputs), "is not in the scalar field");
+    }
+
+    #[test]
+    fn test_raw_format() {
+        assert_eq!(format_proof(PROOF, ProofFormat::Raw).unwrap(), PROOF);
+    }
+
+    #[test]
+    fn test_calldata_format() {
+        // the function signature is the same as the verifier contract
+        let contract = fs::read_to_string(GROTH16_VERIFIER_CONTRACT).unwrap();
+        let params = contract
+            .split("function verifyProof(")
+            .nth(1)
+            .and_then(|s| s.split(')').next())
+            .unwrap();
+        let types: Vec<_> = params
+            .split(',')
+            .filter_map(|param| param.split_whitespace().next())
+            .collect();
+        assert_eq!(
+            format!("verifyProof({})", types.join(",")),
+            VERIFY_PROOF_SIGNATURE
+        );
+
+        // the static arrays of the proof and inputs are encoded in place after the selector
+        let calldata = format_proof(PROOF, ProofFormat::Calldata).unwrap();
+        assert_eq!(calldata.len(), 4 + PROOF_VALUE_COUNT * 32);
+        assert_eq!(hex::encode(&calldata[..4]), "5fe24f23");
+        for index in 0..PROOF_VALUE_COUNT {
+            assert_eq!(word(&calldata[4..], index), word(PROOF, index));
+        }
+    }
+
+    #[test]
+    fn test_json_format() {
+        let json: Value =
+            serde_json::from_slice(&format_proof(PROOF, ProofFormat::Json).unwrap()).unwrap();
+        let w = |index| word(PROOF, index);
+        assert_eq!(
+            json,
+            json!({
+                "a": [w(0), w(1)],
+                "b": [[w(2), w(3)], [w(4), w(5)]],
+                "c": [w(6), w(7)],
+                "inputs": [w(8), w(9)],
+            })
+        );
+
+        // b is in the EIP-197 encoding as [[x1, x0], [y1, y0]]
+        let b = g2_from_raw(&PROOF[64..192]).unwrap();
+        let hex_fq = |value: Fq| format!("0x{}", hex::encode(value.into_bigint().to_bytes_be()));
+        assert_eq!(
+            json["b"],
+            json!([
+                [hex_fq(b.x.c1), hex_fq(b.x.c0)],
+                [hex_fq(b.y.c1), hex_fq(b.y.c0)]
+            ])
+        );
+        assert_eq!(json["inputs"][0], VK_DIGEST);
+        let pv_digest = inputs().pv_digest.to_be_bytes::<32>();
+        assert_eq!(json["inputs"][1], format!("0x{}", hex::encode(pv_digest)));
+    }
+
+    #[test]
+    fn test_wrong_proof_length() {
+        for format in [ProofFormat::Raw, ProofFormat::Calldata, ProofFormat::Json] {
+            assert_err(
+                format_proof(&PROOF[..PROOF.len() - 1], format),
+                "on-chain proof must have 320 bytes, got 319",
+            );
+            assert_err(
+                format_proof(&[PROOF, &[0u8][..]].concat(), format),
+                "on-chain proof must have 320 bytes, got 321",
+            );
+        }
+    }
+
+    #[test]
+    fn test_decode_gnark_proof() {
+        let values: Vec<_> = (0..PROOF_VALUE_COUNT)
+            .map(|index| word(PROOF, index))
+            .collect();
+        let text = format!("\"{}\"\n", values.join(", "));
+        assert_eq!(
+            decode_gnark_proof_to_bytes(&text, Some(PROOF_VALUE_COUNT)).unwrap(),
+            PROOF
+        );
+
+        // the short values are padded to uint256
+        assert_eq!(
+            decode_gnark_proof_to_bytes("0x1,0x2", None).unwrap(),
+            [
+                U256::from(1).to_be_bytes::<32>(),
+                U256::from(2).to_be_bytes::<32>()
+            ]
+            .concat()
+        );
+        assert_err(
+            decode_gnark_proof_to_bytes(&values[1..].join(","), Some(PROOF_VALUE_COUNT)),
+            "gnark proof must have 10 values, got 9",
+        );
+        assert_err(
+            decode_gnark_proof_to_bytes("0x1,0xzz", None),
+            "invalid gnark proof value 0xzz",
+        );
+    }
 }
@@ -0,0 +1,349 @@
+use crate::{
+    AckTaskRequest, FetchAppRequest, ProofResult, ProofTask, SubmitProofRequest, WorkerInfo,
+    app_manager::{App, AppCache},
+    config::WorkerConfig,
+    proving::{
+        PoolTask, TaskRegistry,
+        gateway::GatewayEndpoint,
+        messages::gateway::{GatewayMsg, TaskId},
+        worker::prover::{Prover, ProverRunner, recursion_keys::RecursionKeys},
+    },
+    proving_queue::{ProvingKey, ProvingTask},
+    proving_worker_client::ProvingWorkerClient,
+    task_manager::{TaskEventSender, TaskReporter},
+    utils::auth::AuthConfig,
+};
+use anyhow::Result;
+use pico_vm::thread::channel::DuplexUnboundedChannel;
+use std::{
+    collections::{HashMap, hash_map::Entry},
+    sync::Arc,
+    time::Duration,
+};
+use tokio::{
+    sync::{Mutex, mpsc::unbounded_channel, oneshot},
+    time::{interval, sleep},
+};
+use tonic::{
+    Request, Status,
+    codec::CompressionEncoding,
+    service::interceptor::InterceptedService,
+    transport::{Channel, Endpoint},
+};
+use tracing::{error, info, warn};
+
+// waiting time before retrying a failed request to the gateway
+const RETRY_INTERVAL: Duration = Duration::from_secs(3);
+// maximum attempts of submitting a proof
+const SUBMIT_ATTEMPTS: usize = 3;
+
+/// Remote worker running the local provers for the gateway. It pulls the tasks for the provers by
+/// their `RequestTask` messages, and pushes the generated proofs back to the gateway.
+pub struct RemoteWorker {
+    cfg: WorkerConfig,
+    channel: Channel,
+    worker: WorkerInfo,
+    // tasks in proving, the provers get the keys from here
+    tasks: Arc<TaskRegistry>,
+    // number of the pulled chunks not submitted yet by task, the task is removed from `tasks` once
+    // all of its chunks are submitted
+    pending_chunks: std::sync::Mutex<HashMap<TaskId, usize>>,
+    // fetched applications, bounded by `APP_CACHE_SIZE`
+    apps: AppCache,
+    // held while fetching, so an app is only fetched once
+    fetch_lock: Mutex<()>,
+    // the task events are only reported by gateway, the receiver is dropped
+    event_sender: TaskEventSender,
+}
+
+impl RemoteWorker {
+    pub async fn connect(cfg: WorkerConfig) -> Result<Self> {
+        let channel = Endpoint::from_shared(cfg.gateway_addr.clone())?
+            .connect()
+            .await?;
+        let worker = WorkerInfo {
+            name: cfg
+                .worker_name
+                .clone()
+                .unwrap_or_else(|| format!("worker-{}", std::process::id())),
+            ip: cfg.worker_ip.clone(),
+        };
+        let (event_sender, _) = unbounded_channel();
+        let apps = AppCache::new(cfg.app_cache_size * 1024 * 1024);
+
+        Ok(Self {
+            cfg,
+            channel,
+            worker,
+            tasks: Arc::new(TaskRegistry::default()),
+            pending_chunks: std::sync::Mutex::default(),
+            apps,
+            fetch_lock: Mutex::new(()),
+            event_sender,
+        })
+    }
+
+    fn client(
+        &self,
+    ) -> ProvingWorkerClient<
+        InterceptedService<
+            Channel,
+            impl FnMut(Request<()>) -> Result<Request<()>, Status> + Clone + Send + 'static,
+        >,
+    > {
+        ProvingWorkerClient::with_interceptor(
+            self.channel.clone(),
+            self.cfg.client_auth_interceptor(),
+        )
+        .max_encoding_message_size(self.cfg.max_grpc_msg_size)
+        .max_decoding_message_size(self.cfg.max_grpc_msg_size)
+        .accept_compressed(CompressionEncoding::Zstd)
+        .send_compressed(CompressionEncoding::Zstd)
+    }
+
+    pub async fn run(self) -> Result<()> {
+        let worker_id = self.worker.worker_id();
+        self.client().register_worker(self.worker.clone()).await?;
+        info!(
+            "[{worker_id}] registered to gateway {}",
+            self.cfg.gateway_addr
+        );
+
+        // start provers on a local channel, the worker acts as the gateway of provers
+        let gateway_worker_channel = DuplexUnboundedChannel::default();
+        let endpoint = gateway_worker_channel.endpoint1();
+        let recursion_keys = Arc::new(RecursionKeys::new(self.cfg.recursion_keys_dir.clone()));
+        let _handles: Vec<_> = (0..self.cfg.prover_count)
+            .map(|i| {
+                let prover_id = format!("{worker_id}-prover-{i}");
+                let worker_endpoint = gateway_worker_channel.endpoint2().clone_inner();
+
+                info!("[{worker_id}] creating CPU prover: {}", prover_id);
+                Prover::new(
+                    prover_id,
+                    worker_endpoint,
+                    self.tasks.clone(),
+                    recursion_keys.clone(),
+                )
+                .run()
+            })
+            .collect();
+
+        let worker = Arc::new(self);
+        let heartbeat_worker = worker.clone();
+        tokio::spawn(async move { heartbeat_worker.send_heartbeats().await });
+
+        loop {
+            let receiver = endpoint.clone();
+            let Ok(Ok(msg)) = tokio::task::spawn_blocking(move || receiver.recv()).await else {
+                break;
+            };
+
+            let worker = worker.clone();
+            let endpoint = endpoint.clone();
+            match msg {
+                GatewayMsg::RequestTask => {
+                    tokio::spawn(async move { worker.pull_task(&endpoint).await });
+                }
+                // the assignments are tracked by the remote gateway
+                GatewayMsg::Assigned(..) => (),
+                msg => {
+                    tokio::spawn(async move { worker.submit_proof(msg).await });
+                }
+            }
+        }
+
+        error!("[{worker_id}] provers exited");
+
+        Ok(())
+    }
+
+    // keep sending heartbeats, the tasks held by this worker are re-dispatched by gateway if the
+    // heartbeats are missed
+    async fn send_heartbeats(&self) {
+        let mut interval = interval(Duration::from_secs(self.cfg.heartbeat_interval));
+        loop {
+            interval.tick().await;
+            if let Err(e) = self.client().heartbeat(self.worker.clone()).await {
+                warn!(
+                    "[{}] failed to send heartbeat: {e}",
+                    self.worker.worker_id()
+                );
+            }
+        }
+    }
+
+    // pull a task for an idle prover, it keeps pulling until a task is returned
+    async fn pull_task(&self, endpoint: &GatewayEndpoint) {
+        let worker_id = self.worker.worker_id();
+        loop {
+            let task = match self.client().pull_task(self.worker.clone()).await {
+                Ok(res) => res.into_inner().task,
+                Err(e) => {
+                    warn!("[{worker_id}] failed to pull task: {e}");
+                    sleep(RETRY_INTERVAL).await;
+
+                    // register again in case the gateway restarted
+                    if let Err(e) = self.client().register_worker(self.worker.clone()).await {
+                        warn!("[{worker_id}] failed to register worker: {e}");
+                    }
+                    continue;
+                }
+            };
+            let Some(task) = task else {
+                continue;
+            };
+
+            let (id, task_type, chunk_index) =
+                (task.id.clone(), task.task_type(), task.chunk_index);
+            info!("[{worker_id}] pulled {task_type:?} task-{id} chunk-{chunk_index}");
+
+            // the task is proved even if the ack is lost, its proof is dropped by gateway if it has
+            // been re-dispatched
+            let ack = AckTaskRequest::new(self.worker.clone(), &task);
+            if let Err(e) = self.client().ack_task(ack).await {
+                warn!("[{worker_id}] failed to ack task-{id} chunk-{chunk_index}: {e}");
+            }
+            match self.to_request(task).await {
+                Ok(msg) => {
+                    endpoint.send(msg).unwrap();
+                    return;
+                }
+                // report the failure for retrying by gateway, then pull the next task
+                Err(e) => {
+                    error!("[{worker_id}] failed to load task-{id} chunk-{chunk_index}: {e}");
+                    let result = ProofResult {
+                        id,
+                        task_type: task_type.into(),
+                        chunk_index,
+                        proof: vec![],
+                        error: Some(e.to_string()),
+                        compress_proof: None,
+                    };
+                    self.submit_result(result).await;
+                }
+            }
+        }
+    }
+
+    // register the task of application and convert it to the request for provers
+    async fn to_request(&self, task: ProofTask) -> Result<GatewayMsg> {
+        let task_id: TaskId = task.id.parse()?;
+        let app = self.fetch_app(&task.app_id).await?;
+        let key = ProvingKey::new(task.app_id.clone(), task.id.clone());
+        let wrap = task.wrap();
+        let keep_compress_proof = task.keep_compress_proof();
+        let msg = task.into_request(self.worker.worker_id())?;
+
+        // the task is registered again for each chunk, since the task IDs could be reused after
+        // the gateway restarts
+        let proving_task = ProvingTask::new(
+            key.clone(),
+            app.program,
+            app.pk,
+            app.vk,
+            None,
+            false,
+            None,
+            wrap,
+            keep_compress_proof,
+        );
+        let reporter = TaskReporter::new(key, self.event_sender.clone());
+        let (completion_sender, _) = oneshot::channel();
+        let pool_task = PoolTask::new(proving_task, reporter, completion_sender);
+        self.register_chunk(task_id, pool_task);
+
+        Ok(msg)
+    }
+
+    fn register_chunk(&self, task_id: TaskId, pool_task: PoolTask) {
+        let mut pending_chunks = self.pending_chunks.lock().unwrap();
+        *pending_chunks.entry(task_id).or_default() += 1;
+        self.tasks.insert(task_id, Arc::new(pool_task));
+    }
+
+    // release a submitted chunk, the task is removed with its last pending chunk
+    fn release_chunk(&self, task_id: TaskId) {
+        let mut pending_chunks = self.pending_chunks.lock().unwrap();
+        if let Entry::Occupied(mut entry) = pending_chunks.entry(task_id) {
+            *entry.get_mut() -= 1;
+            if *entry.get() == 0 {
+                entry.remove();
+                self.tasks.remove(&task_id);
+            }
+        }
+    }
+
+    async fn fetch_app(&self, app_id: &str) -> Result<App> {
+        let _guard = self.fetch_lock.lock().await;
+        if let Some(app) = self.apps.get(app_id) {
+            return Ok(app);
+        }
+
+        info!("[{}] fetching app {app_id}", self.worker.worker_id());
+        let req = FetchAppRequest {
+            app_id: app_id.to_string(),
+        };
+        let res = self.client().fetch_app(req).await?.into_inner();
+        let size = res.program.len() + res.pk.len() + res.vk.len();
+        let app = App {
+            app_id: app_id.to_string(),
+            program: Arc::new(bincode::deserialize(&res.program)?),
+            pk: Arc::new(bincode::deserialize(&res.pk)?),
+            vk: Arc::new(bincode::deserialize(&res.vk)?),
+            info: None,
+        };
+        self.apps.insert(app.clone(), size);
+
+        Ok(app)
+    }
+
+    // push back a proof or failure generated by the provers, each pulled chunk has one result
+    async fn submit_proof(&self, msg: GatewayMsg) {
+        if let Some(task_id) = msg.task_id() {
+            self.release_chunk(task_id);
+        }
+
+        let result = match ProofResult::from_response(&msg) {
+            Ok(result) => result,
+            Err(e) => {
+                error!(
+                    "[{}] failed to serialize proof: {e}",
+                    self.worker.worker_id()
+                );
+                return;
+            }
+        };
+
+        self.submit_result(result).await;
+    }
+
+    async fn submit_result(&self, result: ProofResult) {
+        let worker_id = self.worker.worker_id();
+        let (id, task_type, chunk_index) =
+            (result.id.clone(), result.task_type(), result.chunk_index);
+        let req = SubmitProofRequest {
+            worker: Some(self.worker.clone()),
+            result: Some(result),
+        };
+
+        for attempt in 1..=SUBMIT_ATTEMPTS {
+            match self.client().submit_proof(req.clone()).await {
+                Ok(_) => {
+                    info!(
+                        "[{worker_id}] submitted {task_type:?} proof of task-{id} chunk-{chunk_index}"
+                    );
+                    return;
+                }
+                Err(e) => {
+                    warn!(
+                        "[{worker_id}] failed to submit proof of task-{id} chunk-{chunk_index} ({attempt}/{SUBMIT_ATTEMPTS}): {e}"
+                    );
+                    sleep(RETRY_INTERVAL).await;
+                }
+            }
+        }
+
+        error!("[{worker_id}] drop the proof of task-{id} chunk-{chunk_index}");
+    }
+}
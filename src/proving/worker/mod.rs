@@ -1,6 +1,8 @@
 use crate::proving::messages::gateway::GatewayMsg;
 use pico_vm::thread::channel::DuplexUnboundedEndpoint;
 
+pub mod client;
 pub mod prover;
+pub mod service;
 
 pub type WorkerEndpoint = DuplexUnboundedEndpoint<GatewayMsg, GatewayMsg>;
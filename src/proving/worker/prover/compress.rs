@@ -1,3 +1,4 @@
+use super::recursion_keys::{CircuitKeys, RecursionKeys};
 pub(crate) use crate::{
     proving::{
         gateway::handler::proof_tree::IndexedProof,
@@ -36,17 +37,30 @@ use tracing::info;
 pub struct CompressProver {
     prover_id: String,
     machine: CompressMachine<SC, RecursionChipType<Val>>,
+    // the compress circuit verifies the combine proof by the combine machine's base machine
+    combine_machine: CombineMachine<SC, RecursionChipType<Val>>,
+    keys: Arc<RecursionKeys>,
 }
 
 impl CompressProver {
-    pub fn new(prover_id: String) -> Self {
+    pub fn new(prover_id: String, keys: Arc<RecursionKeys>) -> Self {
         let machine = CompressMachine::<_, _>::new(
             SC::compress(),
             RecursionChipType::<Val>::all_chips(),
             RECURSION_NUM_PVS,
         );
+        let combine_machine = CombineMachine::<_, _>::new(
+            SC::default(),
+            RecursionChipType::<Val>::all_chips(),
+            RECURSION_NUM_PVS,
+        );
 
-        Self { prover_id, machine }
+        Self {
+            prover_id,
+            machine,
+            combine_machine,
+            keys,
+        }
     }
 }
 
@@ -76,12 +90,7 @@ impl CompressHandler for CompressProver {
         // Create the compress stdin from the combine proof
         // Note: We need to use the combine machine's base machine, not the compress machine's
         // This is because the compress circuit verifies the combine proof
-        let combine_machine = CombineMachine::<_, _>::new(
-            SC::default(),
-            RecursionChipType::<Val>::all_chips(),
-            RECURSION_NUM_PVS,
-        );
-        let combine_machine_base = combine_machine.base_machine();
+        let combine_machine_base = self.combine_machine.base_machine();
         let compress_stdin = RecursionStdin::new(
             combine_machine_base,
             proof.get_inner().vks.clone(),
@@ -89,40 +98,46 @@ impl CompressHandler for CompressProver {
             true,
             vk_root,
         );
-
-        // Build the compress program
-        let (compress_program, compress_stdin_variant) = if vk_manager.vk_verification_enabled() {
-            let compress_vk_stdin = vk_manager.add_vk_merkle_proof(compress_stdin);
-            let mut compress_program = CompressVkVerifierCircuit::<KoalaBearSimple, SC>::build(
-                combine_machine_base,
-                &compress_vk_stdin,
-            );
-            compress_program.shape = Some(RecursionChipType::<Val>::compress_shape());
-
-            (
-                compress_program,
-                RecursionStdinVariant::WithVk(compress_vk_stdin),
-            )
+        let compress_stdin_variant = if vk_manager.vk_verification_enabled() {
+            RecursionStdinVariant::WithVk(vk_manager.add_vk_merkle_proof(compress_stdin))
         } else {
-            let compress_program = CompressVerifierCircuit::<KoalaBearSimple, SC>::build(
-                combine_machine_base,
-                &compress_stdin,
-            );
-
-            (
-                compress_program,
-                RecursionStdinVariant::NoVk(compress_stdin),
-            )
+            RecursionStdinVariant::NoVk(compress_stdin)
         };
 
-        let compress_program = Arc::new(compress_program);
+        // Build the compress program and setup keys, they're reused if the shape is fixed
+        let keys = self.keys.compress(|| {
+            let compress_program = match &compress_stdin_variant {
+                RecursionStdinVariant::WithVk(compress_vk_stdin) => {
+                    let mut compress_program =
+                        CompressVkVerifierCircuit::<KoalaBearSimple, SC>::build(
+                            combine_machine_base,
+                            compress_vk_stdin,
+                        );
+                    compress_program.shape = Some(RecursionChipType::<Val>::compress_shape());
+                    compress_program
+                }
+                RecursionStdinVariant::NoVk(compress_stdin) => {
+                    CompressVerifierCircuit::<KoalaBearSimple, SC>::build(
+                        combine_machine_base,
+                        compress_stdin,
+                    )
+                }
+            };
+            let (pk, vk) = self.machine.setup_keys(&compress_program);
+
+            CircuitKeys {
+                program: Arc::new(compress_program),
+                pk,
+                vk,
+            }
+        });
 
         // Emulate to get the record
         let mut record = {
             let mut witness_stream = Vec::new();
             Witnessable::<KoalaBearSimple>::write(&compress_stdin_variant, &mut witness_stream);
             let mut runtime = Runtime::<Val, Challenge<SC>, _, _, KOALABEAR_S_BOX_DEGREE>::new(
-                compress_program.clone(),
+                keys.program.clone(),
                 self.machine.config().perm.clone(),
             );
             runtime.witness_stream = witness_stream.into();
@@ -133,10 +148,12 @@ impl CompressHandler for CompressProver {
         // Complement the record
         CompressMachine::<SC, _>::complement_record_static(self.machine.chips(), &mut record);
 
-        // Setup keys and witness
-        let (compress_pk, compress_vk) = self.machine.setup_keys(&compress_program);
-        let compress_witness =
-            ProvingWitness::setup_with_keys_and_records(compress_pk, compress_vk, vec![record]);
+        // Setup witness
+        let compress_witness = ProvingWitness::setup_with_keys_and_records(
+            keys.pk.clone(),
+            keys.vk.clone(),
+            vec![record],
+        );
 
         // Generate the compress proof
         let compress_proof = self.machine.prove(&compress_witness);
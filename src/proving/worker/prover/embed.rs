@@ -1,3 +1,4 @@
+use super::recursion_keys::{CircuitKeys, RecursionKeys};
 use crate::{
     proving::{
         gateway::handler::proof_tree::IndexedProof,
@@ -34,17 +35,30 @@ use tracing::info;
 pub struct EmbedProver {
     prover_id: String,
     machine: EmbedMachine<SC, EmbedSC, RecursionChipType<Val>, Vec<u8>>,
+    // the embed circuit verifies the compress proof by the compress machine's base machine
+    compress_machine: CompressMachine<SC, RecursionChipType<Val>>,
+    keys: Arc<RecursionKeys>,
 }
 
 impl EmbedProver {
-    pub fn new(prover_id: String) -> Self {
+    pub fn new(prover_id: String, keys: Arc<RecursionKeys>) -> Self {
         let machine = EmbedMachine::<_, _, _, _>::new(
             EmbedSC::default(),
             RecursionChipType::<Val>::embed_chips(),
             RECURSION_NUM_PVS,
         );
+        let compress_machine = CompressMachine::<_, _>::new(
+            SC::compress(),
+            RecursionChipType::<Val>::compress_chips(),
+            RECURSION_NUM_PVS,
+        );
 
-        Self { prover_id, machine }
+        Self {
+            prover_id,
+            machine,
+            compress_machine,
+            keys,
+        }
     }
 }
 
@@ -74,12 +88,7 @@ impl EmbedHandler for EmbedProver {
         // Create the embed stdin from the compress proof
         // Note: We need to use the compress machine's base machine, not the embed machine's
         // This is because the embed circuit verifies the compress proof
-        let compress_machine = CompressMachine::<_, _>::new(
-            SC::compress(),
-            RecursionChipType::<Val>::compress_chips(),
-            RECURSION_NUM_PVS,
-        );
-        let compress_machine_base = compress_machine.base_machine();
+        let compress_machine_base = self.compress_machine.base_machine();
         let embed_stdin = RecursionStdin::new(
             compress_machine_base,
             proof.get_inner().vks.clone(),
@@ -87,35 +96,45 @@ impl EmbedHandler for EmbedProver {
             true,
             vk_root,
         );
-
-        // Build the embed program
-        let (embed_program, embed_stdin_variant) = if vk_manager.vk_verification_enabled() {
-            let embed_vk_stdin = vk_manager.add_vk_merkle_proof(embed_stdin);
-            let embed_program = EmbedVkVerifierCircuit::<KoalaBearSimple, SC>::build(
-                compress_machine_base,
-                &embed_vk_stdin,
-                vk_manager,
-            );
-
-            (embed_program, RecursionStdinVariant::WithVk(embed_vk_stdin))
+        let embed_stdin_variant = if vk_manager.vk_verification_enabled() {
+            RecursionStdinVariant::WithVk(vk_manager.add_vk_merkle_proof(embed_stdin))
         } else {
-            let embed_program = EmbedVerifierCircuit::<KoalaBearSimple, SC>::build(
-                compress_machine_base,
-                &embed_stdin,
-            );
-
-            (embed_program, RecursionStdinVariant::NoVk(embed_stdin))
+            RecursionStdinVariant::NoVk(embed_stdin)
         };
 
-        let embed_program = Arc::new(embed_program);
+        // Build the embed program and setup keys, they're reused if the shape is fixed
+        let keys = self.keys.embed(|| {
+            let embed_program = match &embed_stdin_variant {
+                RecursionStdinVariant::WithVk(embed_vk_stdin) => {
+                    EmbedVkVerifierCircuit::<KoalaBearSimple, SC>::build(
+                        compress_machine_base,
+                        embed_vk_stdin,
+                        vk_manager,
+                    )
+                }
+                RecursionStdinVariant::NoVk(embed_stdin) => {
+                    EmbedVerifierCircuit::<KoalaBearSimple, SC>::build(
+                        compress_machine_base,
+                        embed_stdin,
+                    )
+                }
+            };
+            let (pk, vk) = self.machine.setup_keys(&embed_program);
+
+            CircuitKeys {
+                program: Arc::new(embed_program),
+                pk,
+                vk,
+            }
+        });
 
         // Emulate to get the record
         let mut record = {
             let mut witness_stream = Vec::new();
             Witnessable::<KoalaBearSimple>::write(&embed_stdin_variant, &mut witness_stream);
             let mut runtime = Runtime::<Val, Challenge<EmbedSC>, _, _, KOALABEAR_S_BOX_DEGREE>::new(
-                embed_program.clone(),
-                compress_machine.config().perm.clone(),
+                keys.program.clone(),
+                self.compress_machine.config().perm.clone(),
             );
             runtime.witness_stream = witness_stream.into();
             runtime.run().unwrap();
@@ -128,10 +147,12 @@ impl EmbedHandler for EmbedProver {
             &mut record,
         );
 
-        // Setup keys and witness
-        let (embed_pk, embed_vk) = self.machine.setup_keys(&embed_program);
-        let embed_witness =
-            ProvingWitness::setup_with_keys_and_records(embed_pk, embed_vk, vec![record]);
+        // Setup witness
+        let embed_witness = ProvingWitness::setup_with_keys_and_records(
+            keys.pk.clone(),
+            keys.vk.clone(),
+            vec![record],
+        );
 
         // Generate the embed proof
         let embed_proof = self.machine.prove(&embed_witness);
@@ -1,15 +1,24 @@
 pub mod combine;
 pub mod compress;
 pub mod embed;
+pub mod recursion_keys;
 pub mod riscv_convert;
 
 use super::WorkerEndpoint;
 use crate::{
-    proving::messages::{
-        combine::CombineMsg, embed::EmbedRequest, gateway::GatewayMsg, riscv::RiscvMsg,
+    WrapType,
+    proving::{
+        PoolTask, TaskRegistry,
+        messages::{
+            combine::{CombineMsg, CombineRequest},
+            embed::EmbedRequest,
+            gateway::{ChunkFailure, ChunkKey, GatewayMsg, TaskId},
+            riscv::RiscvMsg,
+        },
     },
-    proving_queue::ProvingTask,
+    task_manager::TaskEvent,
     types::{SC, Val},
+    utils::panic::panic_message,
 };
 use combine::{CombineHandler, CombineProver};
 use compress::{CompressHandler, CompressProver};
@@ -19,16 +28,22 @@ use pico_vm::{
     instances::compiler::vk_merkle::{HasStaticVkManager, VkMerkleManager},
     primitives::consts::DIGEST_SIZE,
 };
+use recursion_keys::RecursionKeys;
 use riscv_convert::{RiscvConvertHandler, RiscvConvertProver};
-use std::sync::Arc;
+use std::{
+    panic::{AssertUnwindSafe, catch_unwind},
+    sync::Arc,
+};
 use tokio::task::JoinHandle;
-use tracing::{error, info};
+use tracing::{debug, error, info};
 
 type VkRoot = [Val; DIGEST_SIZE];
 
 pub struct Prover {
     prover_id: String,
     endpoint: Arc<WorkerEndpoint>,
+    // registered tasks for getting the task keys, the chunks of unregistered tasks are dropped
+    tasks: Arc<TaskRegistry>,
     riscv_convert: RiscvConvertProver,
     combine: CombineProver,
     compress: CompressProver,
@@ -37,11 +52,16 @@ pub struct Prover {
 }
 
 impl Prover {
-    pub fn new(prover_id: String, endpoint: Arc<WorkerEndpoint>, task: ProvingTask) -> Self {
-        let riscv_convert = RiscvConvertProver::new(prover_id.clone(), task);
+    pub fn new(
+        prover_id: String,
+        endpoint: Arc<WorkerEndpoint>,
+        tasks: Arc<TaskRegistry>,
+        recursion_keys: Arc<RecursionKeys>,
+    ) -> Self {
+        let riscv_convert = RiscvConvertProver::new(prover_id.clone());
         let combine = CombineProver::new(prover_id.clone());
-        let compress = CompressProver::new(prover_id.clone());
-        let embed = EmbedProver::new(prover_id.clone());
+        let compress = CompressProver::new(prover_id.clone(), recursion_keys.clone());
+        let embed = EmbedProver::new(prover_id.clone(), recursion_keys);
 
         let vk_manager = <SC as HasStaticVkManager>::static_vk_manager();
         let vk_root = get_vk_root(vk_manager);
@@ -49,6 +69,7 @@ impl Prover {
         Self {
             prover_id,
             endpoint,
+            tasks,
             riscv_convert,
             combine,
             compress,
@@ -61,10 +82,126 @@ impl Prover {
     pub fn new_cuda(
         _prover_id: String,
         _endpoint: Arc<WorkerEndpoint>,
-        _task: ProvingTask,
+        _tasks: Arc<TaskRegistry>,
+        _recursion_keys: Arc<RecursionKeys>,
     ) -> Self {
         unimplemented!()
     }
+
+    // get the registered task, return none if the task has completed or failed
+    fn get_task(&self, task_id: TaskId) -> Option<Arc<PoolTask>> {
+        let task = self.tasks.get(&task_id).map(|t| t.value().clone());
+        if task.is_none() {
+            debug!(
+                "[{}] drop the request of unregistered task-{task_id}",
+                self.prover_id
+            );
+        }
+
+        task
+    }
+
+    // notify gateway the request is taken by this prover, the request has been kept by gateway
+    fn assign(&self, key: ChunkKey) {
+        self.endpoint
+            .send(GatewayMsg::Assigned(key, self.prover_id.clone()))
+            .unwrap();
+    }
+
+    // prove the combine request, the compress and embed proofs are generated right after the final
+    // combine proof, the embed proof is skipped if the task is wrapped to STARK
+    fn prove_combine(
+        &self,
+        req: CombineRequest,
+        task: &PoolTask,
+        task_id: TaskId,
+        ip_addr: String,
+    ) -> GatewayMsg {
+        let flag_complete = req.flag_complete;
+        let res = self.combine.process(req);
+        if flag_complete {
+            // Direct execution of compress and embed phases
+            info!(
+                "[{}] final combine complete, executing compress phase directly",
+                self.prover_id
+            );
+            let compress_res = self.compress.process(compress::CompressRequest {
+                chunk_index: res.chunk_index,
+                proof: res.proof.clone(),
+            });
+            task.reporter.report(TaskEvent::Compressed);
+
+            // Verify and send the compress proof as the final proof if wrapped to STARK
+            if task.task.wrap == WrapType::Stark {
+                if let Err(e) = self
+                    .compress
+                    .verify(&compress_res.proof.inner, task.task.vk.as_ref())
+                {
+                    error!("[{}] failed to verify final compress proof", self.prover_id);
+                    let failure = ChunkFailure::Combine(
+                        res.chunk_index,
+                        format!("failed to verify final compress proof: {e}"),
+                    );
+                    return GatewayMsg::ChunkFailed(failure, task_id, ip_addr);
+                }
+                info!(
+                    "[{}] compress complete, sending compress proof of task-{task_id} to gateway",
+                    self.prover_id
+                );
+                return GatewayMsg::Compress(
+                    compress_res.proof.inner.as_ref().clone(),
+                    task_id,
+                    ip_addr,
+                );
+            }
+
+            info!(
+                "[{}] compress complete, executing embed phase directly",
+                self.prover_id
+            );
+            let compress_proof = task
+                .task
+                .keep_intermediate_proofs
+                .then(|| compress_res.proof.inner.as_ref().clone());
+            let embed_res = self.embed.process(EmbedRequest {
+                chunk_index: compress_res.chunk_index,
+                proof: compress_res.proof,
+            });
+
+            // Verify the final embed proof before sending, the final combine is retried if
+            // the verification fails
+            if let Err(e) = self
+                .embed
+                .verify(&embed_res.proof.inner, task.task.vk.as_ref())
+            {
+                error!("[{}] failed to verify final embed proof", self.prover_id);
+                let failure = ChunkFailure::Combine(
+                    res.chunk_index,
+                    format!("failed to verify final embed proof: {e}"),
+                );
+                return GatewayMsg::ChunkFailed(failure, task_id, ip_addr);
+            }
+            info!("[{}] succeeded to verify final embed proof", self.prover_id,);
+            task.reporter.report(TaskEvent::Embedded);
+
+            // Send the embed proof directly to gateway
+            info!(
+                "[{}] embed complete, sending embed proof of task-{task_id} to gateway",
+                self.prover_id
+            );
+            return GatewayMsg::Embed(
+                embed_res.proof.inner.as_ref().clone(),
+                compress_proof,
+                task_id,
+                ip_addr,
+            );
+        }
+        info!(
+            "[{}] send combine response of chunk-{}",
+            self.prover_id, &res.chunk_index,
+        );
+        GatewayMsg::Combine(CombineMsg::Response(res), task_id, ip_addr)
+    }
 }
 
 /// specialization for running emulator on either babybear or koalabear
@@ -78,81 +215,91 @@ impl ProverRunner for Prover {
         info!("[{}] : start", self.prover_id);
 
         tokio::task::spawn_blocking(move || {
-            // request for task first
-            let msg = GatewayMsg::RequestTask;
-            self.endpoint.send(msg).unwrap();
+            loop {
+                // request for the next task
+                let msg = GatewayMsg::RequestTask;
+                self.endpoint.send(msg).unwrap();
 
-            while let Ok(msg) = self.endpoint.recv() {
+                let Ok(msg) = self.endpoint.recv() else {
+                    break;
+                };
                 match msg {
-                    GatewayMsg::Riscv(RiscvMsg::Request(req), task_id, ip_addr) => {
+                    GatewayMsg::Riscv(RiscvMsg::Request(req), task_id, _) => {
                         info!(
-                            "[{}] receive riscv request of chunk-{}",
+                            "[{}] receive riscv request of task-{task_id} chunk-{}",
                             self.prover_id, &req.chunk_index,
                         );
-                        let res = self.riscv_convert.process(req, &self.vk_root);
-                        info!(
-                            "[{}] send riscv response of chunk-{}",
-                            self.prover_id, &res.chunk_index,
-                        );
-                        let msg = GatewayMsg::Riscv(RiscvMsg::Response(res), task_id, ip_addr);
-                        self.endpoint.send(msg).unwrap();
-                    }
-                    GatewayMsg::Combine(CombineMsg::Request(req), task_id, ip_addr) => {
-                        info!(
-                            "[{}] receive combine request of chunk-{}",
-                            self.prover_id, &req.chunk_index,
-                        );
-                        let flag_complete = req.flag_complete;
-                        let res = self.combine.process(req);
-                        if flag_complete {
-                            // Direct execution of compress and embed phases
-                            info!(
-                                "[{}] final combine complete, executing compress phase directly",
-                                self.prover_id
-                            );
-                            let compress_res = self.compress.process(compress::CompressRequest {
-                                chunk_index: res.chunk_index,
-                                proof: res.proof.clone(),
-                            });
-
-                            info!(
-                                "[{}] compress complete, executing embed phase directly",
-                                self.prover_id
+                        let Some(task) = self.get_task(task_id) else {
+                            continue;
+                        };
+
+                        // take the record out of the request kept by gateway without cloning, so
+                        // gateway cannot retry the chunk once the record is consumed by proving
+                        let ip_addr = self.prover_id.clone();
+                        let chunk_index = req.chunk_index;
+                        self.assign(ChunkKey::Riscv(task_id, chunk_index));
+                        let Some(record) = req.record.take() else {
+                            let failure = ChunkFailure::Riscv(
+                                chunk_index,
+                                "riscv record has been consumed".to_string(),
                             );
-                            let embed_res = self.embed.process(EmbedRequest {
-                                chunk_index: compress_res.chunk_index,
-                                proof: compress_res.proof,
-                            });
-
-                            // Verify the final embed proof before sending
-                            if self
-                                .embed
-                                .verify(&embed_res.proof.inner, self.riscv_convert.riscv_vk())
-                                .is_ok()
-                            {
-                                info!("[{}] succeeded to verify final embed proof", self.prover_id,);
-
-                                // Send the embed proof directly to gateway
+                            let msg = GatewayMsg::ChunkFailed(failure, task_id, ip_addr);
+                            self.endpoint.send(msg).unwrap();
+                            continue;
+                        };
+                        let res = catch_unwind(AssertUnwindSafe(|| {
+                            self.riscv_convert.process(
+                                chunk_index,
+                                record,
+                                &task.task.pk,
+                                &task.task.vk,
+                                &self.vk_root,
+                            )
+                        }));
+                        let msg = match res {
+                            Ok(res) => {
                                 info!(
-                                    "[{}] embed complete, sending embed proof to gateway",
-                                    self.prover_id
+                                    "[{}] send riscv response of chunk-{}",
+                                    self.prover_id, &res.chunk_index,
+                                );
+                                GatewayMsg::Riscv(RiscvMsg::Response(res), task_id, ip_addr)
+                            }
+                            Err(e) => {
+                                let reason = panic_message(e);
+                                error!(
+                                    "[{}] riscv of task-{task_id} chunk-{chunk_index} failed: {reason}",
+                                    self.prover_id,
                                 );
-                                let embed_proof_msg =
-                                    GatewayMsg::Embed(embed_res.proof.inner.as_ref().clone());
-                                self.endpoint.send(embed_proof_msg).unwrap();
-
-                                // Send Exit message to complete the workflow
-                                self.endpoint.send(GatewayMsg::Exit).unwrap();
-                                break; // Exit the worker loop
-                            } else {
-                                error!("[{}] failed to verify final embed proof", self.prover_id);
+                                let failure = ChunkFailure::Riscv(chunk_index, reason);
+                                GatewayMsg::ChunkFailed(failure, task_id, ip_addr)
                             }
-                        }
+                        };
+                        self.endpoint.send(msg).unwrap();
+                    }
+                    GatewayMsg::Combine(CombineMsg::Request(req), task_id, _) => {
                         info!(
-                            "[{}] send combine response of chunk-{}",
-                            self.prover_id, &res.chunk_index,
+                            "[{}] receive combine request of task-{task_id} chunk-{}",
+                            self.prover_id, &req.chunk_index,
                         );
-                        let msg = GatewayMsg::Combine(CombineMsg::Response(res), task_id, ip_addr);
+                        let Some(task) = self.get_task(task_id) else {
+                            continue;
+                        };
+
+                        let ip_addr = self.prover_id.clone();
+                        let chunk_index = req.chunk_index;
+                        self.assign(ChunkKey::Combine(task_id, chunk_index));
+                        let res = catch_unwind(AssertUnwindSafe(|| {
+                            self.prove_combine(req, &task, task_id, ip_addr.clone())
+                        }));
+                        let msg = res.unwrap_or_else(|e| {
+                            let reason = panic_message(e);
+                            error!(
+                                "[{}] combine of task-{task_id} chunk-{chunk_index} failed: {reason}",
+                                self.prover_id,
+                            );
+                            let failure = ChunkFailure::Combine(chunk_index, reason);
+                            GatewayMsg::ChunkFailed(failure, task_id, ip_addr)
+                        });
                         self.endpoint.send(msg).unwrap();
                     }
                     // Compress and embed phases are now handled directly in the combine phase
@@ -160,10 +307,6 @@ impl ProverRunner for Prover {
                     GatewayMsg::Exit => break,
                     _ => panic!("unsupported"),
                 }
-
-                // request for the next task
-                let msg = GatewayMsg::RequestTask;
-                self.endpoint.send(msg).unwrap();
             }
         })
     }
@@ -173,7 +316,7 @@ impl ProverRunner for Prover {
     }
 }
 
-fn get_vk_root(vk_manager: &VkMerkleManager<SC>) -> [Val; DIGEST_SIZE] {
+pub(crate) fn get_vk_root(vk_manager: &VkMerkleManager<SC>) -> [Val; DIGEST_SIZE] {
     if vk_manager.vk_verification_enabled() {
         vk_manager.merkle_root
     } else {
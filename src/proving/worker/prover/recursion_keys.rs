@@ -0,0 +1,194 @@
+use crate::types::{EmbedSC, SC, Val};
+use anyhow::Result;
+use pico_vm::{
+    compiler::recursion::program::RecursionProgram,
+    configs::config::StarkGenericConfig,
+    instances::compiler::vk_merkle::HasStaticVkManager,
+    machine::keys::{BaseProvingKey, BaseVerifyingKey},
+    primitives::consts::DIGEST_SIZE,
+};
+use serde::{Serialize, de::DeserializeOwned};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::{Arc, OnceLock},
+    time::Instant,
+};
+use tracing::{info, warn};
+
+/// Program and keys of a compress or embed verifier circuit.
+pub struct CircuitKeys<C: StarkGenericConfig> {
+    pub program: Arc<RecursionProgram<Val>>,
+    pub pk: BaseProvingKey<C>,
+    pub vk: BaseVerifyingKey<C>,
+}
+
+/// Compress and embed circuit keys shared by all provers of a process. The circuit shapes are
+/// fixed when VK verification is enabled, so the programs and keys are only built for the first
+/// proof, and loaded from the keys directory on restart if configured.
+pub struct RecursionKeys {
+    dir: Option<PathBuf>,
+    // vk root of the recursion circuits, the persisted keys are ignored if it changes
+    vk_root: [Val; DIGEST_SIZE],
+    compress: OnceLock<Arc<CircuitKeys<SC>>>,
+    embed: OnceLock<Arc<CircuitKeys<EmbedSC>>>,
+}
+
+impl RecursionKeys {
+    pub fn new(dir: Option<PathBuf>) -> Self {
+        let vk_manager = <SC as HasStaticVkManager>::static_vk_manager();
+        let keys = Self {
+            dir,
+            vk_root: vk_manager.merkle_root,
+            compress: OnceLock::new(),
+            embed: OnceLock::new(),
+        };
+
+        if keys.enabled() {
+            if let Some(compress) = keys.load(COMPRESS_KEYS_FILE) {
+                let _ = keys.compress.set(compress);
+            }
+            if let Some(embed) = keys.load(EMBED_KEYS_FILE) {
+                let _ = keys.embed.set(embed);
+            }
+        }
+
+        keys
+    }
+
+    // the keys are only reused when the circuit shapes are fixed by VK verification
+    fn enabled(&self) -> bool {
+        <SC as HasStaticVkManager>::static_vk_manager().vk_verification_enabled()
+    }
+
+    /// Get the compress keys, they're built by `setup` if not cached.
+    pub fn compress(&self, setup: impl FnOnce() -> CircuitKeys<SC>) -> Arc<CircuitKeys<SC>> {
+        self.get_or_setup(&self.compress, COMPRESS_KEYS_FILE, setup)
+    }
+
+    /// Get the embed keys, they're built by `setup` if not cached.
+    pub fn embed(&self, setup: impl FnOnce() -> CircuitKeys<EmbedSC>) -> Arc<CircuitKeys<EmbedSC>> {
+        self.get_or_setup(&self.embed, EMBED_KEYS_FILE, setup)
+    }
+
+    fn get_or_setup<C: StarkGenericConfig>(
+        &self,
+        cell: &OnceLock<Arc<CircuitKeys<C>>>,
+        file: &str,
+        setup: impl FnOnce() -> CircuitKeys<C>,
+    ) -> Arc<CircuitKeys<C>>
+    where
+        BaseProvingKey<C>: Serialize,
+        BaseVerifyingKey<C>: Serialize,
+    {
+        if !self.enabled() {
+            return Arc::new(setup());
+        }
+
+        // the other provers wait for the first setup instead of building the same keys
+        cell.get_or_init(|| {
+            let start = Instant::now();
+            let keys = Arc::new(setup());
+            info!(
+                "[recursion-keys] setup {file}, time used: {}ms",
+                start.elapsed().as_millis()
+            );
+            self.save(file, &keys);
+            keys
+        })
+        .clone()
+    }
+
+    fn load<C: StarkGenericConfig>(&self, file: &str) -> Option<Arc<CircuitKeys<C>>>
+    where
+        BaseProvingKey<C>: DeserializeOwned,
+        BaseVerifyingKey<C>: DeserializeOwned,
+    {
+        let path = self.dir.as_ref()?.join(file);
+        if !path.exists() {
+            return None;
+        }
+
+        match read_keys(&path) {
+            Ok((vk_root, keys)) if vk_root == self.vk_root => {
+                info!("[recursion-keys] loaded {}", path.display());
+                Some(Arc::new(keys))
+            }
+            Ok(_) => {
+                warn!(
+                    "[recursion-keys] ignore {} of another vk root",
+                    path.display()
+                );
+                None
+            }
+            Err(e) => {
+                warn!("[recursion-keys] failed to load {}: {e}", path.display());
+                None
+            }
+        }
+    }
+
+    fn save<C: StarkGenericConfig>(&self, file: &str, keys: &CircuitKeys<C>)
+    where
+        BaseProvingKey<C>: Serialize,
+        BaseVerifyingKey<C>: Serialize,
+    {
+        let Some(dir) = &self.dir else {
+            return;
+        };
+
+        let path = dir.join(file);
+        match write_keys(dir, &path, &self.vk_root, keys) {
+            Ok(()) => info!("[recursion-keys] saved {}", path.display()),
+            Err(e) => warn!("[recursion-keys] failed to save {}: {e}", path.display()),
+        }
+    }
+}
+
+const COMPRESS_KEYS_FILE: &str = "compress_keys.bin";
+const EMBED_KEYS_FILE: &str = "embed_keys.bin";
+
+type PersistedKeys<C> = (
+    [Val; DIGEST_SIZE],
+    RecursionProgram<Val>,
+    BaseProvingKey<C>,
+    BaseVerifyingKey<C>,
+);
+
+fn read_keys<C: StarkGenericConfig>(path: &Path) -> Result<([Val; DIGEST_SIZE], CircuitKeys<C>)>
+where
+    BaseProvingKey<C>: DeserializeOwned,
+    BaseVerifyingKey<C>: DeserializeOwned,
+{
+    let bytes = fs::read(path)?;
+    let (vk_root, program, pk, vk): PersistedKeys<C> = bincode::deserialize(&bytes)?;
+
+    Ok((
+        vk_root,
+        CircuitKeys {
+            program: Arc::new(program),
+            pk,
+            vk,
+        },
+    ))
+}
+
+// write to a temporary file first, so a partially written file is never loaded
+fn write_keys<C: StarkGenericConfig>(
+    dir: &Path,
+    path: &Path,
+    vk_root: &[Val; DIGEST_SIZE],
+    keys: &CircuitKeys<C>,
+) -> Result<()>
+where
+    BaseProvingKey<C>: Serialize,
+    BaseVerifyingKey<C>: Serialize,
+{
+    fs::create_dir_all(dir)?;
+    let bytes = bincode::serialize(&(vk_root, keys.program.as_ref(), &keys.pk, &keys.vk))?;
+    let tmp_path = path.with_extension("tmp");
+    fs::write(&tmp_path, bytes)?;
+    fs::rename(tmp_path, path)?;
+
+    Ok(())
+}
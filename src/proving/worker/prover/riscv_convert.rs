@@ -1,10 +1,6 @@
 use super::VkRoot;
 use crate::{
-    proving::{
-        gateway::handler::proof_tree::IndexedProof,
-        messages::riscv::{RiscvRequest, RiscvResponse},
-    },
-    proving_queue::ProvingTask,
+    proving::{gateway::handler::proof_tree::IndexedProof, messages::riscv::RiscvResponse},
     types::{SC, Val},
 };
 use log::debug;
@@ -17,7 +13,7 @@ use pico_vm::{
         field_config::KoalaBearSimple,
         stark_config::KoalaBearPoseidon2,
     },
-    emulator::{opts::EmulatorOpts, stdin::EmulatorStdin},
+    emulator::{opts::EmulatorOpts, riscv::record::EmulationRecord, stdin::EmulatorStdin},
     instances::{
         chiptype::{recursion_chiptype::RecursionChipType, riscv_chiptype::RiscvChipType},
         compiler::{
@@ -42,12 +38,10 @@ pub struct RiscvConvertProver {
     recursion_shape_config: Option<RecursionShapeConfig<Val, RecursionChipType<Val>>>,
     riscv_machine: RiscvMachine<SC, RiscvChipType<Val>>,
     convert_machine: ConvertMachine<SC, RecursionChipType<Val>>,
-    pk: BaseProvingKey<SC>,
-    riscv_vk: BaseVerifyingKey<SC>,
 }
 
 impl RiscvConvertProver {
-    pub fn new(prover_id: String, task: ProvingTask) -> Self {
+    pub fn new(prover_id: String) -> Self {
         // opts and setups
         let vk_manager = <SC as HasStaticVkManager>::static_vk_manager();
         let vk_enabled = vk_manager.vk_verification_enabled();
@@ -66,13 +60,7 @@ impl RiscvConvertProver {
         let riscv_machine =
             RiscvMachine::new(SC::default(), RiscvChipType::all_chips(), RISCV_NUM_PVS);
 
-        // Use the program directly from the task since it's already been preprocessed
-        // The program in ProvingTask has already been compiled and preprocessed
-        // let riscv_program = task.program.clone();
-
-        // TODO: see whether to use pk and vk from ProvingTask
-        // let (pk, riscv_vk) = riscv_machine.setup_keys(&riscv_program);
-
+        // pk and vk are provided by each task, since the prover is shared by tasks
         let convert_machine = ConvertMachine::new(
             SC::default(),
             RecursionChipType::<Val>::all_chips(),
@@ -85,30 +73,37 @@ impl RiscvConvertProver {
             recursion_shape_config,
             riscv_machine,
             convert_machine,
-            pk: task.pk.as_ref().clone(),
-            riscv_vk: task.vk.as_ref().clone(),
         }
     }
-
-    pub fn riscv_vk(&self) -> &BaseVerifyingKey<SC> {
-        &self.riscv_vk
-    }
 }
 
 /// specialization for running prover on either babybear or koalabear
 pub trait RiscvConvertHandler {
-    fn process(&self, req: RiscvRequest, vk_root: &VkRoot) -> RiscvResponse;
+    fn process(
+        &self,
+        chunk_index: usize,
+        record: EmulationRecord,
+        pk: &BaseProvingKey<SC>,
+        riscv_vk: &BaseVerifyingKey<SC>,
+        vk_root: &VkRoot,
+    ) -> RiscvResponse;
 }
 
 impl RiscvConvertHandler for RiscvConvertProver {
-    fn process(&self, req: RiscvRequest, vk_root: &VkRoot) -> RiscvResponse {
+    fn process(
+        &self,
+        chunk_index: usize,
+        record: EmulationRecord,
+        pk: &BaseProvingKey<SC>,
+        riscv_vk: &BaseVerifyingKey<SC>,
+        vk_root: &VkRoot,
+    ) -> RiscvResponse {
         log_section("RISCV PHASE");
 
         let mut challenger = self.riscv_machine.config().challenger().clone();
-        self.pk.observed_by(&mut challenger);
+        pk.observed_by(&mut challenger);
 
-        let chunk_index = req.chunk_index;
-        let is_last_chunk = req.record.is_last;
+        let is_last_chunk = record.is_last;
 
         info!(
             "[{}] receive riscv-convert request: chunk_index = {}",
@@ -119,10 +114,10 @@ impl RiscvConvertHandler for RiscvConvertProver {
 
         let proof = self.riscv_machine.prove_record(
             chunk_index,
-            &self.pk,
+            pk,
             &challenger,
             self.riscv_shape_config.as_ref(),
-            req.record,
+            record,
         );
 
         info!("RISCV Phase complete! chunk_index: {}", chunk_index);
@@ -136,7 +131,7 @@ impl RiscvConvertHandler for RiscvConvertProver {
             <KoalaBearSimple as FieldGenericConfig>::F,
             KoalaBearSimple,
         >(
-            &self.riscv_vk,
+            riscv_vk,
             *vk_root,
             [KoalaBear::ZERO; DIGEST_SIZE],
             self.riscv_machine.base_machine(),
@@ -0,0 +1,293 @@
+use super::WorkerEndpoint;
+use crate::{
+    AckTaskRequest, AckTaskResponse, FetchAppRequest, FetchAppResponse, HeartbeatResponse,
+    ProofTask, PullTaskResponse, RegisterWorkerResponse, SubmitProofRequest, SubmitProofResponse,
+    WorkerInfo,
+    app_manager::AppManager,
+    config::ServiceConfig,
+    proving::{
+        ProverPool, TaskRegistry,
+        messages::{
+            combine::CombineMsg,
+            gateway::{ChunkKey, GatewayMsg},
+            riscv::RiscvMsg,
+        },
+    },
+    proving_worker_server::{ProvingWorker, ProvingWorkerServer},
+    task_manager::TaskEvent,
+    utils::auth::AuthConfig,
+};
+use crossbeam::channel::RecvTimeoutError;
+use dashmap::DashMap;
+use std::{net::SocketAddr, sync::Arc, time::Duration};
+use tokio::{signal::ctrl_c, task::JoinHandle, time::Instant};
+use tonic::{
+    Request, Response, Status, async_trait, codec::CompressionEncoding,
+    service::interceptor::InterceptedService, transport::Server,
+};
+use tracing::{debug, info, warn};
+
+// maximum waiting time of a task pulling, the worker pulls again if no task is returned
+const TASK_POLL_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// gRPC service for the remote workers. It takes the tasks from the gateway channel as the local
+/// provers do, and sends the proofs pushed by the workers back to the gateway.
+pub struct WorkerService {
+    cfg: ServiceConfig,
+    endpoint: Arc<WorkerEndpoint>,
+    tasks: Arc<TaskRegistry>,
+    app_manager: AppManager,
+    // registered remote workers by worker ID
+    workers: DashMap<String, WorkerInfo>,
+}
+
+impl WorkerService {
+    pub fn new(cfg: ServiceConfig, app_manager: AppManager, pool: &ProverPool) -> Self {
+        Self {
+            cfg,
+            endpoint: pool.worker_endpoint(),
+            tasks: pool.tasks(),
+            app_manager,
+            workers: DashMap::new(),
+        }
+    }
+
+    pub fn run(self, addr: SocketAddr) -> JoinHandle<()> {
+        info!("[worker-service] grpc server init on {addr}");
+        let handle = tokio::spawn(async move {
+            let max_grpc_msg_size = self.cfg.max_grpc_msg_size;
+            let auth_interceptor = self.cfg.server_auth_interceptor();
+
+            let svc = InterceptedService::new(
+                ProvingWorkerServer::new(self)
+                    .max_encoding_message_size(max_grpc_msg_size)
+                    .max_decoding_message_size(max_grpc_msg_size)
+                    .accept_compressed(CompressionEncoding::Zstd)
+                    .send_compressed(CompressionEncoding::Zstd),
+                auth_interceptor,
+            );
+
+            Server::builder()
+                .add_service(svc)
+                .serve_with_shutdown(addr, async {
+                    ctrl_c().await.expect("failed to wait for shutdown");
+                })
+                .await
+                .expect("failed");
+        });
+
+        info!("[worker-service] grpc server init end");
+
+        handle
+    }
+
+    fn send_to_gateway(&self, msg: GatewayMsg) -> Result<(), Status> {
+        self.endpoint
+            .send(msg)
+            .map_err(|_| Status::unavailable("gateway exited"))
+    }
+
+    fn check_worker(&self, worker: &WorkerInfo) -> Result<String, Status> {
+        let worker_id = worker.worker_id();
+        if !self.workers.contains_key(&worker_id) {
+            return Err(Status::failed_precondition(format!(
+                "worker {worker_id} is not registered"
+            )));
+        }
+
+        Ok(worker_id)
+    }
+
+    // take the next request from the gateway channel, it returns none if no task is ready in time
+    async fn next_task(&self, worker_id: &str) -> Result<Option<ProofTask>, Status> {
+        let deadline = Instant::now() + TASK_POLL_TIMEOUT;
+        loop {
+            let timeout = deadline.saturating_duration_since(Instant::now());
+            let endpoint = self.endpoint.clone();
+            let tasks = self.tasks.clone();
+            let ip_addr = worker_id.to_string();
+
+            // lease the request to the worker right after it's taken, so it's re-dispatched by
+            // gateway if the worker is dead, or the response is never received by the worker, the
+            // request is serialized in the blocking thread as well
+            let task = tokio::task::spawn_blocking(move || -> Result<_, RecvTimeoutError> {
+                let msg = endpoint
+                    .receiver()
+                    .recv_timeout(timeout)?
+                    .with_ip_addr(ip_addr.clone());
+                if let Some(key) = ChunkKey::of_request(&msg) {
+                    let _ = endpoint.send(GatewayMsg::Leased(key, ip_addr));
+                }
+
+                let task_id = match &msg {
+                    GatewayMsg::Riscv(RiscvMsg::Request(_), task_id, _)
+                    | GatewayMsg::Combine(CombineMsg::Request(_), task_id, _) => *task_id,
+                    _ => {
+                        warn!("[worker-service] drop unsupported message from gateway");
+                        return Ok(None);
+                    }
+                };
+
+                // drop the request of unregistered task as the local provers do
+                let Some(task) = tasks.get(&task_id).map(|t| t.value().clone()) else {
+                    debug!("[worker-service] drop the request of unregistered task-{task_id}");
+                    return Ok(None);
+                };
+                Ok(Some((task_id, ProofTask::from_request(&msg, &task.task))))
+            })
+            .await
+            .map_err(|e| Status::internal(format!("failed to pull task: {e}")))?;
+            let (task_id, task) = match task {
+                Ok(Some(task)) => task,
+                Ok(None) => continue,
+                Err(RecvTimeoutError::Timeout) => return Ok(None),
+                Err(RecvTimeoutError::Disconnected) => {
+                    return Err(Status::unavailable("gateway exited"));
+                }
+            };
+            let task =
+                task.map_err(|e| Status::internal(format!("failed to serialize task: {e}")))?;
+
+            info!(
+                "[worker-service] dispatch {:?} of task-{task_id} chunk-{} to {worker_id}",
+                task.task_type(),
+                task.chunk_index,
+            );
+
+            return Ok(Some(task));
+        }
+    }
+}
+
+#[async_trait]
+impl ProvingWorker for WorkerService {
+    // register a remote worker
+    async fn register_worker(
+        &self,
+        req: Request<WorkerInfo>,
+    ) -> Result<Response<RegisterWorkerResponse>, Status> {
+        let worker = req.into_inner();
+        let worker_id = worker.worker_id();
+        info!("[worker-service] register worker {worker_id}");
+
+        self.workers.insert(worker_id.clone(), worker);
+        self.send_to_gateway(GatewayMsg::Heartbeat(worker_id))?;
+
+        Ok(Response::new(RegisterWorkerResponse {}))
+    }
+
+    // keep a remote worker alive
+    async fn heartbeat(
+        &self,
+        req: Request<WorkerInfo>,
+    ) -> Result<Response<HeartbeatResponse>, Status> {
+        let worker = req.into_inner();
+        let worker_id = self.check_worker(&worker)?;
+        debug!("[worker-service] receive heartbeat of worker {worker_id}");
+
+        self.send_to_gateway(GatewayMsg::Heartbeat(worker_id))?;
+
+        Ok(Response::new(HeartbeatResponse {}))
+    }
+
+    // pull the next riscv or combine task
+    async fn pull_task(
+        &self,
+        req: Request<WorkerInfo>,
+    ) -> Result<Response<PullTaskResponse>, Status> {
+        let worker = req.into_inner();
+        let worker_id = self.check_worker(&worker)?;
+
+        let task = self.next_task(&worker_id).await?;
+
+        Ok(Response::new(PullTaskResponse { task }))
+    }
+
+    // acknowledge a pulled task, so it's held by the worker until its heartbeats are missed
+    async fn ack_task(
+        &self,
+        req: Request<AckTaskRequest>,
+    ) -> Result<Response<AckTaskResponse>, Status> {
+        let req = req.into_inner();
+        let worker = req
+            .worker
+            .as_ref()
+            .ok_or_else(|| Status::invalid_argument("missing worker"))?;
+        let worker_id = self.check_worker(worker)?;
+        let key = req
+            .chunk_key()
+            .map_err(|e| Status::invalid_argument(e.to_string()))?;
+        debug!("[worker-service] receive ack of {key:?} from {worker_id}");
+
+        self.send_to_gateway(GatewayMsg::Acked(key, worker_id))?;
+
+        Ok(Response::new(AckTaskResponse {}))
+    }
+
+    // push back the proof or the failure of a pulled task
+    async fn submit_proof(
+        &self,
+        req: Request<SubmitProofRequest>,
+    ) -> Result<Response<SubmitProofResponse>, Status> {
+        let SubmitProofRequest { worker, result } = req.into_inner();
+        let worker = worker.ok_or_else(|| Status::invalid_argument("missing worker"))?;
+        let result = result.ok_or_else(|| Status::invalid_argument("missing proof result"))?;
+        let worker_id = self.check_worker(&worker)?;
+
+        let task_id = result
+            .task_id()
+            .map_err(|e| Status::invalid_argument(e.to_string()))?;
+        let task_type = result.task_type();
+        let chunk_index = result.chunk_index as usize;
+        info!(
+            "[worker-service] receive {task_type:?} proof of task-{task_id} chunk-{chunk_index} from {worker_id}"
+        );
+
+        // the failed request is re-dispatched by gateway
+        let msg = result
+            .into_response(worker_id)
+            .map_err(|e| Status::invalid_argument(format!("invalid proof: {e}")))?;
+
+        // the compress and embed events are reported by the local provers in a single node
+        match msg {
+            GatewayMsg::Compress(..) => {
+                if let Some(task) = self.tasks.get(&task_id) {
+                    task.reporter.report(TaskEvent::Compressed);
+                }
+            }
+            GatewayMsg::Embed(..) => {
+                if let Some(task) = self.tasks.get(&task_id) {
+                    task.reporter.report(TaskEvent::Compressed);
+                    task.reporter.report(TaskEvent::Embedded);
+                }
+            }
+            _ => (),
+        }
+
+        self.send_to_gateway(msg)?;
+
+        Ok(Response::new(SubmitProofResponse {}))
+    }
+
+    // fetch the serialized program and keys of an application
+    async fn fetch_app(
+        &self,
+        req: Request<FetchAppRequest>,
+    ) -> Result<Response<FetchAppResponse>, Status> {
+        let app_id = req.into_inner().app_id;
+        info!("[worker-service] fetch app {app_id}");
+
+        let row = self
+            .app_manager
+            .get_app_row(&app_id)
+            .await
+            .map_err(|e| Status::internal(format!("failed to get app: {e}")))?
+            .ok_or_else(|| Status::not_found(format!("app {app_id} not found")))?;
+
+        Ok(Response::new(FetchAppResponse {
+            program: row.program,
+            pk: row.pk,
+            vk: row.vk,
+        }))
+    }
+}
@@ -1,18 +1,29 @@
 use crate::{
+    ProofStage, TaskStatus, WrapType,
+    app_manager::AppManager,
     config::ServiceConfig,
-    proving,
+    proving::ProverPool,
+    scheduler::TaskScheduler,
+    task_manager::{TaskEvent, TaskEventSender, TaskManager, TaskReporter, finish_task},
     types::{DbPool, SC},
 };
-use crossbeam::channel::Receiver;
+use anyhow::{Result, anyhow};
 use dashmap::DashMap;
 use derive_more::Constructor;
 use pico_vm::{
     compiler::riscv::program::Program,
     machine::keys::{BaseProvingKey, BaseVerifyingKey},
 };
-use std::sync::Arc;
-use tokio::{task::JoinHandle, time::Instant};
-use tracing::{error, info};
+use std::{
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use tokio::{
+    sync::{OwnedSemaphorePermit, Semaphore, watch},
+    task::JoinHandle,
+    time::{Instant, interval, sleep_until},
+};
+use tracing::{error, info, warn};
 
 #[derive(Constructor, Debug, Eq, Hash, PartialEq, Clone)]
 pub struct ProvingKey {
@@ -38,21 +49,101 @@ pub struct ProvingTask {
     pub vk: Arc<BaseVerifyingKey<SC>>,
     pub inputs: Option<Vec<u8>>,
     pub use_gpu: bool,
+    // unix timestamp (seconds), the task fails if it's not proved before the deadline
+    pub deadline: Option<u64>,
+    // final stage of the proof
+    pub wrap: WrapType,
+    // persist the compress and embed proofs for downloading
+    pub keep_intermediate_proofs: bool,
 }
 
-#[derive(Constructor)]
+/// Serialized compress or embed proof with its verifying key.
+#[derive(Constructor, Clone)]
+pub struct IntermediateProof {
+    pub stage: ProofStage,
+    pub proof: Vec<u8>,
+    pub vk: Vec<u8>,
+}
+
+#[derive(Constructor, Clone)]
 pub struct ProvingOutput {
     pub proof: Arc<[u8]>,
+    // sha256 of the public values stream masked to 253 bits, 32 bytes of big-endian
+    pub pv_digest: Vec<u8>,
+    // public values stream committed by the program
+    pub pv_stream: Arc<[u8]>,
+    // riscv vk digest with 0x prefix, it's the same as the app ID
+    pub vk_digest: String,
+    pub wrap: WrapType,
+    // only stored in database, it's taken out before caching the output in memory
+    pub intermediate_proofs: Vec<IntermediateProof>,
 }
 
 pub type ProvingOutputs = DashMap<ProvingKey, ProvingOutput>;
 
+// interval of failing the queued tasks whose deadline has expired
+const DEADLINE_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Cancellation signal of an in-flight task, it's checked by the emulator and awaited by the
+/// prover pool.
+#[derive(Clone)]
+pub struct CancelSignal(watch::Receiver<bool>);
+
+impl CancelSignal {
+    pub fn is_cancelled(&self) -> bool {
+        *self.0.borrow()
+    }
+
+    // wait until the task is cancelled, it never returns if the task is unregistered
+    pub async fn cancelled(&mut self) {
+        if self.0.wait_for(|cancelled| *cancelled).await.is_err() {
+            std::future::pending::<()>().await
+        }
+    }
+}
+
+/// Cancellation senders of the in-flight tasks. A queued task is removed from the scheduler
+/// instead.
+#[derive(Default)]
+pub struct TaskCancellations(DashMap<ProvingKey, watch::Sender<bool>>);
+
+impl TaskCancellations {
+    // register a task before proving, the previous signal of the same key is replaced
+    pub fn register(&self, key: &ProvingKey) -> CancelSignal {
+        let (sender, receiver) = watch::channel(false);
+        self.0.insert(key.clone(), sender);
+
+        CancelSignal(receiver)
+    }
+
+    // unregister a task once its proving returns, it's skipped if a re-proving task of the same
+    // key has been registered
+    pub fn unregister(&self, key: &ProvingKey, signal: &CancelSignal) {
+        self.0
+            .remove_if(key, |_, sender| sender.subscribe().same_channel(&signal.0));
+    }
+
+    // send the cancellation signal, return false if the task is not in proving
+    pub fn cancel(&self, key: &ProvingKey) -> bool {
+        self.0
+            .get(key)
+            .map(|sender| {
+                sender.send_replace(true);
+            })
+            .is_some()
+    }
+}
+
 #[derive(Constructor)]
 pub struct ProvingQueue {
     cfg: ServiceConfig,
     outputs: Arc<ProvingOutputs>,
-    receiver: Arc<Receiver<ProvingTask>>,
+    scheduler: Arc<TaskScheduler>,
     db_pool: Arc<DbPool>,
+    event_sender: TaskEventSender,
+    cancellations: Arc<TaskCancellations>,
+    // provers are shared by the concurrent tasks
+    pool: Arc<ProverPool>,
 }
 
 impl ProvingQueue {
@@ -63,92 +154,399 @@ impl ProvingQueue {
     pub fn run(&self) -> JoinHandle<()> {
         info!("[proving-network] proving queue init");
 
-        let cfg = self.cfg.clone();
-        let receiver = self.receiver.clone();
+        let scheduler = self.scheduler.clone();
         let outputs = self.outputs.clone();
         let db_pool = self.db_pool.clone();
+        let event_sender = self.event_sender.clone();
+        let cancellations = self.cancellations.clone();
+        let proving_timeout = self.cfg.proving_timeout.map(Duration::from_secs);
+        let pool = self.pool.clone();
+        let max_concurrent_tasks = self.cfg.max_concurrent_tasks.max(1);
+        let semaphore = Arc::new(Semaphore::new(max_concurrent_tasks));
+        info!("[proving-network] max concurrent tasks: {max_concurrent_tasks}");
+
+        // the queued tasks are expired regardless of the free proving slots
+        tokio::spawn(Self::expire_queued_tasks(
+            scheduler.clone(),
+            db_pool.clone(),
+            event_sender.clone(),
+        ));
 
         let handle = tokio::spawn(async move {
             loop {
-                let task = match tokio::task::block_in_place(|| receiver.recv()) {
-                    Ok(task) => task,
-                    Err(_) => {
-                        info!("[proving-network] channel closed, exiting queue loop");
-                        break;
+                // wait for a free proving slot before taking the next task
+                let permit = semaphore
+                    .clone()
+                    .acquire_owned()
+                    .await
+                    .expect("semaphore is never closed");
+
+                let task = scheduler.pop().await;
+                info!(
+                    "[proving-network] scheduled task {:?}, remaining queued tasks: {}",
+                    task.key,
+                    scheduler.len()
+                );
+
+                tokio::spawn(Self::process_task(
+                    task,
+                    pool.clone(),
+                    outputs.clone(),
+                    db_pool.clone(),
+                    event_sender.clone(),
+                    cancellations.clone(),
+                    proving_timeout,
+                    permit,
+                ));
+            }
+        });
+
+        info!("[proving-network] proving queue init end");
+
+        handle
+    }
+
+    // periodically remove the queued tasks whose deadline has expired and fail them
+    async fn expire_queued_tasks(
+        scheduler: Arc<TaskScheduler>,
+        db_pool: Arc<DbPool>,
+        event_sender: TaskEventSender,
+    ) {
+        let mut ticker = interval(DEADLINE_CHECK_INTERVAL);
+        loop {
+            ticker.tick().await;
+
+            let now = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs();
+            for task in scheduler.remove_expired(now) {
+                warn!(
+                    "[proving-network] deadline expired for queued task: {:?}",
+                    task.key
+                );
+
+                let reason = "proving timeout: deadline expired in queue".to_string();
+                match Self::store_failure_in_db(&db_pool, &task.key, &reason).await {
+                    Ok(true) => (),
+                    Ok(false) => {
+                        info!("[proving-network] proving task cancelled: {:?}", task.key);
+                        continue;
+                    }
+                    Err(e) => error!(
+                        "[proving-network] failed to store failure in database for task {:?}: {}",
+                        task.key, e
+                    ),
+                }
+
+                TaskReporter::new(task.key, event_sender.clone()).report(TaskEvent::Failed(reason));
+            }
+        }
+    }
+
+    // prove a task and store the result, the proving slot is released when it returns
+    #[allow(clippy::too_many_arguments)]
+    async fn process_task(
+        task: ProvingTask,
+        pool: Arc<ProverPool>,
+        outputs: Arc<ProvingOutputs>,
+        db_pool: Arc<DbPool>,
+        event_sender: TaskEventSender,
+        cancellations: Arc<TaskCancellations>,
+        proving_timeout: Option<Duration>,
+        _permit: OwnedSemaphorePermit,
+    ) {
+        let task_key = task.key.clone();
+
+        // register the cancellation signal before checking the status, so a cancellation racing
+        // with the scheduling is either found in database or received by the signal
+        let cancel = cancellations.register(&task_key);
+        match TaskManager::new(db_pool.clone()).get_task(&task_key).await {
+            Ok(Some(row)) if row.status() == TaskStatus::Cancelled => {
+                info!("[proving-network] skip cancelled task: {:?}", task_key);
+                cancellations.unregister(&task_key, &cancel);
+                return;
+            }
+            Ok(_) => (),
+            Err(e) => warn!(
+                "[proving-network] failed to get status of task {:?}: {}",
+                task_key, e
+            ),
+        }
+
+        info!("[proving-network] starting proving task: {:?}", task_key);
+
+        let reporter = TaskReporter::new(task_key.clone(), event_sender.clone());
+        reporter.report(TaskEvent::Status(TaskStatus::Emulating));
+
+        // Run the real proving workflow with database pool
+        info!("[proving-network] calling prove_task for: {:?}", task_key);
+        let start = Instant::now();
+        let deadline = proving_deadline(task.deadline, proving_timeout, start);
+        let mut timed_out = false;
+        let result = if deadline.is_some_and(|deadline| deadline <= start) {
+            Err(anyhow!("proving timeout: deadline expired before proving"))
+        } else {
+            let proving = pool.prove_task(task, reporter.clone(), cancel.clone());
+            tokio::pin!(proving);
+
+            // abort the task by the cancellation signal once the deadline expires, and wait for
+            // the prover pool to release it
+            tokio::select! {
+                res = &mut proving => res,
+                _ = async {
+                    match deadline {
+                        Some(deadline) => sleep_until(deadline).await,
+                        None => std::future::pending().await,
                     }
-                };
-                let task_key = task.key.clone();
-                info!("[proving-network] starting proving task: {:?}", task_key);
-
-                // Run the real proving workflow with database pool
-                info!("[proving-network] calling prove_task for: {:?}", task_key);
-                let start = Instant::now();
-                let result = proving::prove_task(task, cfg.prover_count).await;
+                } => {
+                    warn!("[proving-network] proving timeout for task: {:?}", task_key);
+                    timed_out = true;
+                    cancellations.cancel(&task_key);
+                    let _ = proving.await;
+                    Err(anyhow!(
+                        "proving timeout after {:.1} seconds",
+                        start.elapsed().as_secs_f32()
+                    ))
+                }
+            }
+        };
+        info!(
+            "[proving-network] prove_task returned for {:?}, proving time : {}",
+            task_key,
+            start.elapsed().as_secs_f32(),
+        );
+        cancellations.unregister(&task_key, &cancel);
+
+        // the task has been marked cancelled in database, drop the result
+        if cancel.is_cancelled() && !timed_out {
+            info!("[proving-network] proving task cancelled: {:?}", task_key);
+            return;
+        }
+
+        match result {
+            Ok(mut output) => {
                 info!(
-                    "[proving-network] prove_task returned for {:?}, proving time : {}",
+                    "[proving-network] proving completed successfully for task: {:?}, proof size: {} bytes",
                     task_key,
-                    start.elapsed().as_secs_f32(),
+                    output.proof.len()
                 );
 
-                match result {
-                    Ok(proof_bytes) => {
-                        info!(
-                            "[proving-network] proving completed successfully for task: {:?}, proof size: {} bytes",
-                            task_key,
-                            proof_bytes.len()
-                        );
-
-                        // Store proof in memory for quick access
-                        let proof_arc: Arc<[u8]> = Arc::from(proof_bytes);
-                        let output = ProvingOutput::new(proof_arc.clone());
-                        let _ = outputs.insert(task_key.clone(), output);
-                        info!(
-                            "[proving-network] proof stored in memory for task: {:?}, total memory entries: {}",
-                            task_key,
-                            outputs.len()
-                        );
+                let intermediate_proofs = std::mem::take(&mut output.intermediate_proofs);
 
-                        // Store proof in database
-                        if let Err(e) =
-                            Self::store_proof_in_db(&db_pool, &task_key, &proof_arc).await
-                        {
-                            error!(
-                                "[proving-network] failed to store proof in database for task {:?}: {}",
-                                task_key, e
-                            );
-                        } else {
-                            info!(
-                                "[proving-network] proof stored in database for task: {:?}",
-                                task_key
-                            );
-                        }
+                // Store proof in database, it's dropped if the task is cancelled in the meantime
+                match Self::store_proof_in_db(&db_pool, &task_key, &output).await {
+                    Ok(true) => info!(
+                        "[proving-network] proof stored in database for task: {:?}",
+                        task_key
+                    ),
+                    Ok(false) => {
+                        info!("[proving-network] proving task cancelled: {:?}", task_key);
+                        return;
                     }
-                    Err(e) => {
+                    Err(e) => error!(
+                        "[proving-network] failed to store proof in database for task {:?}: {}",
+                        task_key, e
+                    ),
+                }
+
+                // Store proof in memory for quick access
+                let _ = outputs.insert(task_key.clone(), output.clone());
+                info!(
+                    "[proving-network] proof stored in memory for task: {:?}, total memory entries: {}",
+                    task_key,
+                    outputs.len()
+                );
+
+                if !intermediate_proofs.is_empty() {
+                    if let Err(e) = Self::store_intermediate_proofs_in_db(
+                        &db_pool,
+                        &task_key,
+                        &intermediate_proofs,
+                    )
+                    .await
+                    {
                         error!(
-                            "[proving-network] failed to prove task {:?}: {}",
+                            "[proving-network] failed to store intermediate proofs in database for task {:?}: {}",
                             task_key, e
                         );
                     }
                 }
+
+                reporter.report(TaskEvent::Status(TaskStatus::Succeeded));
             }
-        });
+            Err(e) => {
+                error!(
+                    "[proving-network] failed to prove task {:?}: {}",
+                    task_key, e
+                );
 
-        info!("[proving-network] proving queue init end");
+                // Store failure in database for the result polling
+                let reason = e.to_string();
+                match Self::store_failure_in_db(&db_pool, &task_key, &reason).await {
+                    Ok(true) => (),
+                    Ok(false) => {
+                        info!("[proving-network] proving task cancelled: {:?}", task_key);
+                        return;
+                    }
+                    Err(e) => error!(
+                        "[proving-network] failed to store failure in database for task {:?}: {}",
+                        task_key, e
+                    ),
+                }
 
-        handle
+                reporter.report(TaskEvent::Failed(reason));
+            }
+        }
     }
 
+    // save the proof with the succeeded status in one transaction, return false if the task has
+    // been cancelled
     async fn store_proof_in_db(
         db_pool: &Arc<DbPool>,
         key: &ProvingKey,
-        proof: &[u8],
+        output: &ProvingOutput,
+    ) -> Result<bool, sqlx::Error> {
+        let mut tx = db_pool.begin().await?;
+        if !finish_task(&mut tx, key, TaskStatus::Succeeded, None).await? {
+            return Ok(false);
+        }
+
+        sqlx::query(
+            "INSERT OR REPLACE INTO proofs \
+             (app_id, task_id, proof, status, error, pv_stream, pv_digest, vk_digest, wrap) \
+             VALUES (?, ?, ?, ?, NULL, ?, ?, ?, ?)",
+        )
+        .bind(key.app_id())
+        .bind(key.task_id())
+        .bind(&*output.proof)
+        .bind(TaskStatus::Succeeded.as_str_name())
+        .bind(&*output.pv_stream)
+        .bind(&output.pv_digest)
+        .bind(&output.vk_digest)
+        .bind(output.wrap.as_str_name())
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+
+        Ok(true)
+    }
+
+    async fn store_intermediate_proofs_in_db(
+        db_pool: &Arc<DbPool>,
+        key: &ProvingKey,
+        proofs: &[IntermediateProof],
     ) -> Result<(), sqlx::Error> {
-        sqlx::query("INSERT OR REPLACE INTO proofs (app_id, task_id, proof) VALUES (?, ?, ?)")
+        for proof in proofs {
+            sqlx::query(
+                "INSERT OR REPLACE INTO intermediate_proofs (app_id, task_id, stage, proof, vk) \
+                 VALUES (?, ?, ?, ?, ?)",
+            )
             .bind(key.app_id())
             .bind(key.task_id())
-            .bind(proof)
+            .bind(proof.stage.as_str_name())
+            .bind(&proof.proof)
+            .bind(&proof.vk)
             .execute(&**db_pool)
             .await?;
+        }
         Ok(())
     }
+
+    // save the failure with the failed status in one transaction, return false if the task has
+    // been cancelled
+    async fn store_failure_in_db(
+        db_pool: &Arc<DbPool>,
+        key: &ProvingKey,
+        reason: &str,
+    ) -> Result<bool, sqlx::Error> {
+        let mut tx = db_pool.begin().await?;
+        if !finish_task(&mut tx, key, TaskStatus::Failed, Some(reason)).await? {
+            return Ok(false);
+        }
+
+        sqlx::query(
+            "INSERT OR REPLACE INTO proofs (app_id, task_id, proof, status, error) \
+             VALUES (?, ?, NULL, ?, ?)",
+        )
+        .bind(key.app_id())
+        .bind(key.task_id())
+        .bind(TaskStatus::Failed.as_str_name())
+        .bind(reason)
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+
+        Ok(true)
+    }
+}
+
+/// Re-enqueue the tasks which were queued or in-progress before the service restarted, the
+/// interrupted tasks are proved again from the beginning.
+pub async fn restore_tasks(
+    db_pool: Arc<DbPool>,
+    app_manager: &AppManager,
+    scheduler: &TaskScheduler,
+) -> Result<usize> {
+    let task_manager = TaskManager::new(db_pool.clone());
+
+    let rows = task_manager.unfinished_tasks().await?;
+    info!(
+        "[proving-network] restoring {} unfinished tasks",
+        rows.len()
+    );
+
+    let mut restored = 0;
+    for row in rows {
+        let key = row.key();
+        let Some(app) = app_manager.get_app(key.app_id()).await? else {
+            warn!(
+                "[proving-network] cannot find app to restore task {:?}",
+                key
+            );
+            let reason = format!("cannot find app {} to restore task", key.app_id());
+            // the task is marked failed with the failure saved
+            ProvingQueue::store_failure_in_db(&db_pool, &key, &reason).await?;
+            continue;
+        };
+
+        task_manager.requeue_task(&key).await?;
+        let wrap = row.wrap();
+        let task = ProvingTask::new(
+            key,
+            app.program,
+            app.pk,
+            app.vk,
+            row.inputs,
+            row.use_gpu,
+            row.deadline.map(|t| t as u64),
+            wrap,
+            row.keep_intermediate_proofs,
+        );
+        scheduler.push(task, row.priority);
+        restored += 1;
+    }
+
+    Ok(restored)
+}
+
+// the earlier one of the requested deadline and the default timeout since proving starts
+fn proving_deadline(
+    deadline: Option<u64>,
+    proving_timeout: Option<Duration>,
+    start: Instant,
+) -> Option<Instant> {
+    let deadline = deadline.map(|deadline| {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        start + Duration::from_secs(deadline.saturating_sub(now))
+    });
+    let timeout = proving_timeout.map(|timeout| start + timeout);
+
+    match (deadline, timeout) {
+        (Some(deadline), Some(timeout)) => Some(deadline.min(timeout)),
+        (deadline, timeout) => deadline.or(timeout),
+    }
 }
@@ -0,0 +1,375 @@
+use crate::proving_queue::{ProvingKey, ProvingTask};
+use std::{
+    cmp::Reverse,
+    collections::{BTreeMap, HashMap, VecDeque},
+    sync::Mutex,
+};
+use tokio::sync::Notify;
+use tracing::debug;
+
+/// Task queued by the scheduler, it's identified by the proving key.
+pub trait QueuedTask {
+    fn key(&self) -> &ProvingKey;
+
+    // unix timestamp (seconds) after which the task is dropped from the queue
+    fn deadline(&self) -> Option<u64>;
+}
+
+impl QueuedTask for ProvingTask {
+    fn key(&self) -> &ProvingKey {
+        &self.key
+    }
+
+    fn deadline(&self) -> Option<u64> {
+        self.deadline
+    }
+}
+
+// queued tasks of the same priority, the apps are served in round-robin
+struct PriorityLevel<T> {
+    // app IDs in serving order, an app is removed once it has no queued task
+    apps: VecDeque<String>,
+    // queued tasks of each app in submission order
+    tasks: HashMap<String, VecDeque<T>>,
+}
+
+impl<T> Default for PriorityLevel<T> {
+    fn default() -> Self {
+        Self {
+            apps: VecDeque::new(),
+            tasks: HashMap::new(),
+        }
+    }
+}
+
+impl<T: QueuedTask> PriorityLevel<T> {
+    fn push(&mut self, task: T) {
+        let app_id = task.key().app_id().to_string();
+        let tasks = self.tasks.entry(app_id.clone()).or_default();
+        if tasks.is_empty() {
+            self.apps.push_back(app_id);
+        }
+        tasks.push_back(task);
+    }
+
+    fn pop(&mut self) -> Option<T> {
+        let app_id = self.apps.pop_front()?;
+        let tasks = self.tasks.get_mut(&app_id)?;
+        let task = tasks.pop_front();
+
+        // move the app to the end if it has more tasks
+        if tasks.is_empty() {
+            self.tasks.remove(&app_id);
+        } else {
+            self.apps.push_back(app_id);
+        }
+
+        task
+    }
+
+    fn remove(&mut self, key: &ProvingKey) -> bool {
+        let Some(tasks) = self.tasks.get_mut(key.app_id()) else {
+            return false;
+        };
+        let Some(index) = tasks.iter().position(|task| task.key() == key) else {
+            return false;
+        };
+        tasks.remove(index);
+
+        if tasks.is_empty() {
+            self.tasks.remove(key.app_id());
+            self.apps.retain(|app_id| app_id != key.app_id());
+        }
+
+        true
+    }
+
+    // remove the tasks whose deadline is not after the timestamp, in submission order per app
+    fn remove_expired(&mut self, now: u64) -> Vec<T> {
+        let mut expired = vec![];
+        for tasks in self.tasks.values_mut() {
+            let mut index = 0;
+            while index < tasks.len() {
+                if tasks[index]
+                    .deadline()
+                    .is_some_and(|deadline| deadline <= now)
+                {
+                    expired.extend(tasks.remove(index));
+                } else {
+                    index += 1;
+                }
+            }
+        }
+
+        self.tasks.retain(|_, tasks| !tasks.is_empty());
+        self.apps.retain(|app_id| self.tasks.contains_key(app_id));
+
+        expired
+    }
+
+    fn len(&self) -> usize {
+        self.tasks.values().map(VecDeque::len).sum()
+    }
+
+    // number of tasks popped before the task in this level
+    fn position(&self, key: &ProvingKey) -> Option<usize> {
+        let tasks = self.tasks.get(key.app_id())?;
+        let index = tasks.iter().position(|task| task.key() == key)?;
+
+        // each round pops one task of every app in serving order
+        let mut position = 0;
+        for app_id in &self.apps {
+            let len = self.tasks[app_id].len();
+            if app_id == key.app_id() {
+                position += index;
+            } else if self.apps_before(app_id, key.app_id()) {
+                position += len.min(index + 1);
+            } else {
+                position += len.min(index);
+            }
+        }
+
+        Some(position)
+    }
+
+    // identify if the app is served before the other in each round
+    fn apps_before(&self, app_id: &str, other: &str) -> bool {
+        self.apps.iter().position(|id| id == app_id) < self.apps.iter().position(|id| id == other)
+    }
+}
+
+/// Scheduler of the queued proving tasks. The tasks of higher priority are popped first, and the
+/// apps with the same priority are served in round-robin, so one app cannot starve the others.
+pub struct TaskScheduler<T = ProvingTask> {
+    levels: Mutex<BTreeMap<Reverse<u32>, PriorityLevel<T>>>,
+    notify: Notify,
+}
+
+impl<T> Default for TaskScheduler<T> {
+    fn default() -> Self {
+        Self {
+            levels: Mutex::new(BTreeMap::new()),
+            notify: Notify::new(),
+        }
+    }
+}
+
+impl<T: QueuedTask> TaskScheduler<T> {
+    pub fn push(&self, task: T, priority: u32) {
+        debug!(
+            "[scheduler] push task {:?} with priority {}",
+            task.key(),
+            priority
+        );
+        self.levels
+            .lock()
+            .unwrap()
+            .entry(Reverse(priority))
+            .or_default()
+            .push(task);
+        self.notify.notify_one();
+    }
+
+    // wait for the next task to prove
+    pub async fn pop(&self) -> T {
+        loop {
+            // register the waiter before checking, so a push in-between is not missed
+            let notified = self.notify.notified();
+            if let Some(task) = self.try_pop() {
+                return task;
+            }
+            notified.await;
+        }
+    }
+
+    fn try_pop(&self) -> Option<T> {
+        let mut levels = self.levels.lock().unwrap();
+        let mut entry = levels.first_entry()?;
+        let task = entry.get_mut().pop();
+        if entry.get().apps.is_empty() {
+            entry.remove();
+        }
+
+        task
+    }
+
+    // remove a queued task, return false if the task is not in the queue
+    pub fn remove(&self, key: &ProvingKey) -> bool {
+        let mut levels = self.levels.lock().unwrap();
+        let removed = levels.values_mut().any(|level| level.remove(key));
+        levels.retain(|_, level| !level.apps.is_empty());
+
+        removed
+    }
+
+    // remove the queued tasks whose deadline has expired at the unix timestamp (seconds)
+    pub fn remove_expired(&self, now: u64) -> Vec<T> {
+        let mut levels = self.levels.lock().unwrap();
+        let expired = levels
+            .values_mut()
+            .flat_map(|level| level.remove_expired(now))
+            .collect();
+        levels.retain(|_, level| !level.apps.is_empty());
+
+        expired
+    }
+
+    // return the number of tasks which will be popped before this one, none if it's not queued
+    pub fn position(&self, key: &ProvingKey) -> Option<usize> {
+        let levels = self.levels.lock().unwrap();
+
+        let mut ahead = 0;
+        for level in levels.values() {
+            if let Some(position) = level.position(key) {
+                return Some(ahead + position);
+            }
+            ahead += level.len();
+        }
+
+        None
+    }
+
+    pub fn len(&self) -> usize {
+        self.levels
+            .lock()
+            .unwrap()
+            .values()
+            .map(PriorityLevel::<T>::len)
+            .sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct TestTask(ProvingKey, Option<u64>);
+
+    impl QueuedTask for TestTask {
+        fn key(&self) -> &ProvingKey {
+            &self.0
+        }
+
+        fn deadline(&self) -> Option<u64> {
+            self.1
+        }
+    }
+
+    fn key(app_id: &str, task_id: &str) -> ProvingKey {
+        ProvingKey::new(app_id.to_string(), task_id.to_string())
+    }
+
+    fn push(scheduler: &TaskScheduler<TestTask>, app_id: &str, task_id: &str, priority: u32) {
+        scheduler.push(TestTask(key(app_id, task_id), None), priority);
+    }
+
+    fn pop_all(scheduler: &TaskScheduler<TestTask>) -> Vec<String> {
+        std::iter::from_fn(|| scheduler.try_pop())
+            .map(|task| task.0.task_id().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_higher_priority_first() {
+        let scheduler = TaskScheduler::default();
+        push(&scheduler, "app-a", "low", 0);
+        push(&scheduler, "app-a", "high", 5);
+        push(&scheduler, "app-b", "middle", 1);
+
+        assert_eq!(pop_all(&scheduler), ["high", "middle", "low"]);
+        assert!(scheduler.is_empty());
+    }
+
+    #[test]
+    fn test_round_robin_apps() {
+        let scheduler = TaskScheduler::default();
+        push(&scheduler, "app-a", "a1", 0);
+        push(&scheduler, "app-a", "a2", 0);
+        push(&scheduler, "app-a", "a3", 0);
+        push(&scheduler, "app-b", "b1", 0);
+        push(&scheduler, "app-b", "b2", 0);
+
+        assert_eq!(pop_all(&scheduler), ["a1", "b1", "a2", "b2", "a3"]);
+    }
+
+    #[test]
+    fn test_position_after_removal() {
+        let scheduler = TaskScheduler::default();
+        push(&scheduler, "app-a", "a1", 0);
+        push(&scheduler, "app-a", "a2", 0);
+        push(&scheduler, "app-b", "b1", 0);
+        push(&scheduler, "app-b", "b2", 0);
+        push(&scheduler, "app-c", "c1", 1);
+
+        // the task of higher priority is ahead of all the others
+        assert_eq!(scheduler.position(&key("app-c", "c1")), Some(0));
+        assert_eq!(scheduler.position(&key("app-a", "a1")), Some(1));
+        assert_eq!(scheduler.position(&key("app-b", "b1")), Some(2));
+        assert_eq!(scheduler.position(&key("app-a", "a2")), Some(3));
+        assert_eq!(scheduler.position(&key("app-b", "b2")), Some(4));
+
+        assert!(scheduler.remove(&key("app-b", "b1")));
+        assert!(!scheduler.remove(&key("app-b", "b1")));
+        assert_eq!(scheduler.position(&key("app-b", "b1")), None);
+        assert_eq!(scheduler.position(&key("app-b", "b2")), Some(2));
+        assert_eq!(scheduler.position(&key("app-a", "a2")), Some(3));
+
+        assert!(scheduler.remove(&key("app-c", "c1")));
+        assert!(scheduler.remove(&key("app-a", "a1")));
+        assert_eq!(scheduler.position(&key("app-a", "a2")), Some(0));
+        assert_eq!(scheduler.position(&key("app-b", "b2")), Some(1));
+        assert_eq!(pop_all(&scheduler), ["a2", "b2"]);
+    }
+
+    #[test]
+    fn test_empty_levels_dropped() {
+        let scheduler = TaskScheduler::default();
+        push(&scheduler, "app-a", "a1", 2);
+        push(&scheduler, "app-a", "a2", 1);
+        push(&scheduler, "app-b", "b1", 0);
+
+        // emptied by pop
+        assert_eq!(
+            scheduler.try_pop().map(|task| task.0),
+            Some(key("app-a", "a1"))
+        );
+        assert_eq!(scheduler.levels.lock().unwrap().len(), 2);
+
+        // emptied by removal
+        assert!(scheduler.remove(&key("app-b", "b1")));
+        assert_eq!(scheduler.levels.lock().unwrap().len(), 1);
+
+        assert_eq!(pop_all(&scheduler), ["a2"]);
+        assert!(scheduler.levels.lock().unwrap().is_empty());
+        assert_eq!(scheduler.position(&key("app-a", "a2")), None);
+    }
+
+    #[test]
+    fn test_remove_expired() {
+        let scheduler = TaskScheduler::default();
+        scheduler.push(TestTask(key("app-a", "a1"), Some(100)), 1);
+        scheduler.push(TestTask(key("app-a", "a2"), None), 0);
+        scheduler.push(TestTask(key("app-a", "a3"), Some(50)), 0);
+        scheduler.push(TestTask(key("app-b", "b1"), Some(200)), 0);
+        scheduler.push(TestTask(key("app-c", "c1"), Some(100)), 0);
+
+        let mut expired: Vec<_> = scheduler
+            .remove_expired(100)
+            .into_iter()
+            .map(|task| task.0.task_id().to_string())
+            .collect();
+        expired.sort();
+        assert_eq!(expired, ["a1", "a3", "c1"]);
+
+        // the level emptied by the expiry is dropped, and the app without queued tasks is no
+        // longer served
+        assert_eq!(scheduler.levels.lock().unwrap().len(), 1);
+        assert_eq!(scheduler.position(&key("app-b", "b1")), Some(1));
+        assert!(scheduler.remove_expired(100).is_empty());
+        assert_eq!(pop_all(&scheduler), ["a2", "b1"]);
+    }
+}
@@ -0,0 +1,545 @@
+use crate::{
+    GetTaskStatusResponse, TaskEventType, TaskProgress, TaskStatus, WatchTaskResponse, WrapType,
+    proving_queue::ProvingKey, types::DbPool,
+};
+use anyhow::Result;
+use derive_more::Constructor;
+use sqlx::{FromRow, SqliteConnection};
+use std::{
+    sync::Arc,
+    time::{SystemTime, UNIX_EPOCH},
+};
+use tokio::{
+    sync::{
+        broadcast,
+        mpsc::{UnboundedReceiver, UnboundedSender},
+    },
+    task::JoinHandle,
+};
+use tracing::{debug, error, info};
+
+/// Lifecycle event of a proving task, reported by the proving queue and the gateway.
+#[derive(Clone, Debug)]
+pub enum TaskEvent {
+    // status transition
+    Status(TaskStatus),
+    // proving failed with the reason
+    Failed(String),
+    // emulation of the batch number has been done
+    BatchEmulated(usize),
+    // an emulation record of the chunk index has been generated
+    ChunkEmulated(usize),
+    // riscv-convert proof of the chunk index has been generated
+    ChunkProved(usize),
+    // combine proof of the chunk range `[start, end]` has been generated
+    ChunksCombined(usize, usize),
+    // compress proof has been generated
+    Compressed,
+    // embed proof has been generated
+    Embedded,
+}
+
+impl TaskEvent {
+    // convert to the watching response, `status` is the current task status and it's replaced if
+    // this event changes the status
+    pub fn to_response(&self, status: TaskStatus) -> WatchTaskResponse {
+        let mut res = WatchTaskResponse {
+            status: status.into(),
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or_default(),
+            ..Default::default()
+        };
+
+        let event = match self {
+            Self::Status(TaskStatus::Queued) => {
+                res.status = TaskStatus::Queued.into();
+                TaskEventType::Accepted
+            }
+            Self::Status(TaskStatus::Succeeded) => {
+                res.status = TaskStatus::Succeeded.into();
+                TaskEventType::ProofReady
+            }
+            Self::Status(status) => {
+                res.status = (*status).into();
+                TaskEventType::StatusChanged
+            }
+            Self::Failed(reason) => {
+                res.status = TaskStatus::Failed.into();
+                res.failure_reason = Some(reason.clone());
+                TaskEventType::Failed
+            }
+            Self::BatchEmulated(batch) => {
+                res.index = Some(*batch as u64);
+                TaskEventType::BatchEmulated
+            }
+            Self::ChunkEmulated(chunk_index) => {
+                res.index = Some(*chunk_index as u64);
+                TaskEventType::ChunkEmulated
+            }
+            Self::ChunkProved(chunk_index) => {
+                res.index = Some(*chunk_index as u64);
+                TaskEventType::ChunkProved
+            }
+            Self::ChunksCombined(start, end) => {
+                res.chunk_start = Some(*start as u64);
+                res.chunk_end = Some(*end as u64);
+                TaskEventType::ChunksCombined
+            }
+            Self::Compressed => TaskEventType::Compressed,
+            Self::Embedded => TaskEventType::Embedded,
+        };
+        res.event = event.into();
+
+        res
+    }
+}
+
+pub type TaskEventSender = UnboundedSender<(ProvingKey, TaskEvent)>;
+pub type TaskEventReceiver = UnboundedReceiver<(ProvingKey, TaskEvent)>;
+// capacity of the event broadcast channel, the slow watchers skip the lagged events
+pub const EVENT_BROADCAST_CAPACITY: usize = 4096;
+
+// publish the saved events to the watchers
+pub type TaskEventBroadcaster = broadcast::Sender<(ProvingKey, TaskEvent)>;
+
+/// Reporter bound to a single task, it could be used from both async and blocking threads.
+#[derive(Clone, Constructor)]
+pub struct TaskReporter {
+    key: ProvingKey,
+    sender: TaskEventSender,
+}
+
+impl TaskReporter {
+    pub fn report(&self, event: TaskEvent) {
+        // the receiver is only dropped when the service exits
+        let _ = self.sender.send((self.key.clone(), event));
+    }
+}
+
+impl TaskStatus {
+    // identify if the task is succeeded, failed or cancelled
+    pub fn is_finished(&self) -> bool {
+        matches!(self, Self::Succeeded | Self::Failed | Self::Cancelled)
+    }
+}
+
+#[derive(Debug, FromRow)]
+pub struct TaskRow {
+    pub app_id: String,
+    pub task_id: String,
+    pub status: String,
+    pub error: Option<String>,
+    pub chunks_emulated: i64,
+    pub chunks_proved: i64,
+    pub combine_proofs: i64,
+    pub created_at: i64,
+    pub started_at: Option<i64>,
+    pub finished_at: Option<i64>,
+    pub updated_at: i64,
+}
+
+impl TaskRow {
+    pub fn status(&self) -> TaskStatus {
+        TaskStatus::from_str_name(&self.status).unwrap_or(TaskStatus::Unknown)
+    }
+}
+
+impl From<&TaskRow> for WatchTaskResponse {
+    fn from(row: &TaskRow) -> Self {
+        Self {
+            err: None,
+            event: TaskEventType::Snapshot.into(),
+            status: row.status().into(),
+            index: None,
+            chunk_start: None,
+            chunk_end: None,
+            failure_reason: row.error.clone(),
+            timestamp: row.updated_at as u64,
+        }
+    }
+}
+
+impl From<TaskRow> for GetTaskStatusResponse {
+    fn from(row: TaskRow) -> Self {
+        let progress = TaskProgress {
+            chunks_emulated: row.chunks_emulated as u64,
+            chunks_proved: row.chunks_proved as u64,
+            combine_proofs: row.combine_proofs as u64,
+        };
+
+        Self {
+            err: None,
+            status: row.status().into(),
+            failure_reason: row.error,
+            progress: Some(progress),
+            created_at: row.created_at as u64,
+            started_at: row.started_at.map(|t| t as u64),
+            finished_at: row.finished_at.map(|t| t as u64),
+            updated_at: row.updated_at as u64,
+            queue_position: None,
+        }
+    }
+}
+
+/// Submitted task data required to restore a proving task.
+#[derive(Debug, FromRow)]
+pub struct SubmittedTaskRow {
+    pub app_id: String,
+    pub task_id: String,
+    pub inputs: Option<Vec<u8>>,
+    pub use_gpu: bool,
+    pub priority: u32,
+    pub deadline: Option<i64>,
+    pub wrap: String,
+    pub keep_intermediate_proofs: bool,
+}
+
+impl SubmittedTaskRow {
+    pub fn key(&self) -> ProvingKey {
+        ProvingKey::new(self.app_id.clone(), self.task_id.clone())
+    }
+
+    pub fn wrap(&self) -> WrapType {
+        WrapType::from_str_name(&self.wrap).unwrap_or(WrapType::Groth16)
+    }
+}
+
+/// Result of saving a submitted task.
+#[derive(Debug, PartialEq)]
+pub enum TaskInsertion {
+    Inserted,
+    // the task has been submitted before
+    Submitted,
+    // the app is deleted after it's fetched for the task
+    AppNotFound,
+}
+
+#[derive(Constructor)]
+pub struct TaskManager {
+    db_pool: Arc<DbPool>,
+}
+
+impl TaskManager {
+    pub async fn get_task(&self, key: &ProvingKey) -> Result<Option<TaskRow>> {
+        let row = sqlx::query_as::<_, TaskRow>(
+            "SELECT app_id, task_id, status, error, chunks_emulated, chunks_proved, \
+             combine_proofs, created_at, started_at, finished_at, updated_at \
+             FROM tasks WHERE app_id = ? AND task_id = ?",
+        )
+        .bind(key.app_id())
+        .bind(key.task_id())
+        .fetch_optional(&*self.db_pool)
+        .await?;
+
+        Ok(row)
+    }
+
+    pub async fn get_submitted_task(&self, key: &ProvingKey) -> Result<Option<SubmittedTaskRow>> {
+        let row = sqlx::query_as::<_, SubmittedTaskRow>(
+            "SELECT app_id, task_id, inputs, use_gpu, priority, deadline, wrap, \
+             keep_intermediate_proofs FROM tasks \
+             WHERE app_id = ? AND task_id = ?",
+        )
+        .bind(key.app_id())
+        .bind(key.task_id())
+        .fetch_optional(&*self.db_pool)
+        .await?;
+
+        Ok(row)
+    }
+
+    // save a new submitted task as queued
+    #[allow(clippy::too_many_arguments)]
+    pub async fn add_task(
+        &self,
+        key: &ProvingKey,
+        inputs: Option<&[u8]>,
+        use_gpu: bool,
+        priority: u32,
+        deadline: Option<u64>,
+        wrap: WrapType,
+        keep_intermediate_proofs: bool,
+    ) -> Result<TaskInsertion> {
+        let mut tx = self.db_pool.begin().await?;
+
+        // the task is written before checking the app, so the app couldn't be deleted in between
+        let res = sqlx::query(
+            "INSERT INTO tasks \
+             (app_id, task_id, status, inputs, use_gpu, priority, deadline, wrap, \
+             keep_intermediate_proofs) \
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?) ON CONFLICT (app_id, task_id) DO NOTHING",
+        )
+        .bind(key.app_id())
+        .bind(key.task_id())
+        .bind(TaskStatus::Queued.as_str_name())
+        .bind(inputs)
+        .bind(use_gpu)
+        .bind(priority)
+        .bind(deadline.map(|t| t as i64))
+        .bind(wrap.as_str_name())
+        .bind(keep_intermediate_proofs)
+        .execute(&mut *tx)
+        .await?;
+        if res.rows_affected() == 0 {
+            return Ok(TaskInsertion::Submitted);
+        }
+        if !app_exists(&mut tx, key.app_id()).await? {
+            return Ok(TaskInsertion::AppNotFound);
+        }
+
+        tx.commit().await?;
+
+        Ok(TaskInsertion::Inserted)
+    }
+
+    // replace a submitted task as queued for re-proving, the previous result is removed
+    #[allow(clippy::too_many_arguments)]
+    pub async fn replace_task(
+        &self,
+        key: &ProvingKey,
+        inputs: Option<&[u8]>,
+        use_gpu: bool,
+        priority: u32,
+        deadline: Option<u64>,
+        wrap: WrapType,
+        keep_intermediate_proofs: bool,
+    ) -> Result<TaskInsertion> {
+        let mut tx = self.db_pool.begin().await?;
+
+        sqlx::query("DELETE FROM proofs WHERE app_id = ? AND task_id = ?")
+            .bind(key.app_id())
+            .bind(key.task_id())
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query("DELETE FROM intermediate_proofs WHERE app_id = ? AND task_id = ?")
+            .bind(key.app_id())
+            .bind(key.task_id())
+            .execute(&mut *tx)
+            .await?;
+
+        sqlx::query(
+            "INSERT OR REPLACE INTO tasks \
+             (app_id, task_id, status, inputs, use_gpu, priority, deadline, wrap, \
+             keep_intermediate_proofs) \
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)",
+        )
+        .bind(key.app_id())
+        .bind(key.task_id())
+        .bind(TaskStatus::Queued.as_str_name())
+        .bind(inputs)
+        .bind(use_gpu)
+        .bind(priority)
+        .bind(deadline.map(|t| t as i64))
+        .bind(wrap.as_str_name())
+        .bind(keep_intermediate_proofs)
+        .execute(&mut *tx)
+        .await?;
+        if !app_exists(&mut tx, key.app_id()).await? {
+            return Ok(TaskInsertion::AppNotFound);
+        }
+
+        tx.commit().await?;
+
+        Ok(TaskInsertion::Inserted)
+    }
+
+    // return the tasks which are queued or in-progress, ordered by submission
+    pub async fn unfinished_tasks(&self) -> Result<Vec<SubmittedTaskRow>> {
+        let rows = sqlx::query_as::<_, SubmittedTaskRow>(
+            "SELECT app_id, task_id, inputs, use_gpu, priority, deadline, wrap, \
+             keep_intermediate_proofs FROM tasks \
+             WHERE status NOT IN (?, ?, ?) ORDER BY created_at, rowid",
+        )
+        .bind(TaskStatus::Succeeded.as_str_name())
+        .bind(TaskStatus::Failed.as_str_name())
+        .bind(TaskStatus::Cancelled.as_str_name())
+        .fetch_all(&*self.db_pool)
+        .await?;
+
+        Ok(rows)
+    }
+
+    // reset an interrupted task to queued, the previous progress is discarded
+    pub async fn requeue_task(&self, key: &ProvingKey) -> Result<()> {
+        sqlx::query(
+            "UPDATE tasks SET status = ?, error = NULL, chunks_emulated = 0, chunks_proved = 0, \
+             combine_proofs = 0, started_at = NULL, updated_at = strftime('%s', 'now') \
+             WHERE app_id = ? AND task_id = ?",
+        )
+        .bind(TaskStatus::Queued.as_str_name())
+        .bind(key.app_id())
+        .bind(key.task_id())
+        .execute(&*self.db_pool)
+        .await?;
+
+        Ok(())
+    }
+
+    // mark an unfinished task cancelled and save the cancellation as its result, return false if
+    // the task has finished
+    pub async fn cancel_task(&self, key: &ProvingKey) -> Result<bool> {
+        let mut tx = self.db_pool.begin().await?;
+
+        let res = sqlx::query(
+            "UPDATE tasks SET status = ?, finished_at = strftime('%s', 'now'), \
+             updated_at = strftime('%s', 'now') \
+             WHERE app_id = ? AND task_id = ? AND status NOT IN (?, ?, ?)",
+        )
+        .bind(TaskStatus::Cancelled.as_str_name())
+        .bind(key.app_id())
+        .bind(key.task_id())
+        .bind(TaskStatus::Succeeded.as_str_name())
+        .bind(TaskStatus::Failed.as_str_name())
+        .bind(TaskStatus::Cancelled.as_str_name())
+        .execute(&mut *tx)
+        .await?;
+        if res.rows_affected() == 0 {
+            return Ok(false);
+        }
+
+        sqlx::query(
+            "INSERT OR REPLACE INTO proofs (app_id, task_id, proof, status, error) \
+             VALUES (?, ?, NULL, ?, ?)",
+        )
+        .bind(key.app_id())
+        .bind(key.task_id())
+        .bind(TaskStatus::Cancelled.as_str_name())
+        .bind("task cancelled")
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(true)
+    }
+
+    pub fn run(
+        self,
+        mut receiver: TaskEventReceiver,
+        broadcaster: TaskEventBroadcaster,
+    ) -> JoinHandle<()> {
+        info!("[proving-network] task manager init");
+
+        let handle = tokio::spawn(async move {
+            while let Some((key, event)) = receiver.recv().await {
+                debug!("[task-manager] task {:?} event: {:?}", key, event);
+                if let Err(e) = self.process_event(&key, &event).await {
+                    error!(
+                        "[task-manager] failed to save event {:?} of task {:?}: {}",
+                        event, key, e
+                    );
+                }
+
+                // publish after saving, so the watchers could read the status from database
+                // without missing the events, it fails only if there is no watcher
+                let _ = broadcaster.send((key, event));
+            }
+            info!("[proving-network] event channel closed, exiting task manager");
+        });
+
+        info!("[proving-network] task manager init end");
+
+        handle
+    }
+
+    async fn process_event(&self, key: &ProvingKey, event: &TaskEvent) -> Result<()> {
+        let query = match event {
+            TaskEvent::Status(status) => {
+                let sql = match status {
+                    TaskStatus::Emulating => {
+                        "UPDATE tasks SET status = ?, started_at = strftime('%s', 'now'), \
+                         updated_at = strftime('%s', 'now') \
+                         WHERE app_id = ? AND task_id = ? AND status NOT IN (?, ?, ?)"
+                    }
+                    TaskStatus::Succeeded => {
+                        "UPDATE tasks SET status = ?, finished_at = strftime('%s', 'now'), \
+                         updated_at = strftime('%s', 'now') \
+                         WHERE app_id = ? AND task_id = ? AND status NOT IN (?, ?, ?)"
+                    }
+                    _ => {
+                        "UPDATE tasks SET status = ?, updated_at = strftime('%s', 'now') \
+                         WHERE app_id = ? AND task_id = ? AND status NOT IN (?, ?, ?)"
+                    }
+                };
+                sqlx::query(sql).bind(status.as_str_name())
+            }
+            TaskEvent::Failed(reason) => sqlx::query(
+                "UPDATE tasks SET status = ?, error = ?, finished_at = strftime('%s', 'now'), \
+                 updated_at = strftime('%s', 'now') \
+                 WHERE app_id = ? AND task_id = ? AND status NOT IN (?, ?, ?)",
+            )
+            .bind(TaskStatus::Failed.as_str_name())
+            .bind(reason),
+            TaskEvent::ChunkEmulated(_) => sqlx::query(
+                "UPDATE tasks SET chunks_emulated = chunks_emulated + 1, \
+                 updated_at = strftime('%s', 'now') \
+                 WHERE app_id = ? AND task_id = ? AND status NOT IN (?, ?, ?)",
+            ),
+            TaskEvent::ChunkProved(_) => sqlx::query(
+                "UPDATE tasks SET chunks_proved = chunks_proved + 1, \
+                 updated_at = strftime('%s', 'now') \
+                 WHERE app_id = ? AND task_id = ? AND status NOT IN (?, ?, ?)",
+            ),
+            TaskEvent::ChunksCombined(..) => sqlx::query(
+                "UPDATE tasks SET combine_proofs = combine_proofs + 1, \
+                 updated_at = strftime('%s', 'now') \
+                 WHERE app_id = ? AND task_id = ? AND status NOT IN (?, ?, ?)",
+            ),
+            // only published to the watchers
+            TaskEvent::BatchEmulated(_) | TaskEvent::Compressed | TaskEvent::Embedded => {
+                return Ok(());
+            }
+        };
+
+        // the late events of a finished task are ignored, e.g. the success of a task cancelled
+        // right before its proof is saved
+        query
+            .bind(key.app_id())
+            .bind(key.task_id())
+            .bind(TaskStatus::Succeeded.as_str_name())
+            .bind(TaskStatus::Failed.as_str_name())
+            .bind(TaskStatus::Cancelled.as_str_name())
+            .execute(&*self.db_pool)
+            .await?;
+
+        Ok(())
+    }
+}
+
+/// Mark an unfinished task succeeded or failed in the transaction saving its result, return false if
+/// the task has finished, e.g. it's cancelled before the result is saved.
+pub async fn finish_task(
+    conn: &mut SqliteConnection,
+    key: &ProvingKey,
+    status: TaskStatus,
+    error: Option<&str>,
+) -> Result<bool, sqlx::Error> {
+    let res = sqlx::query(
+        "UPDATE tasks SET status = ?, error = ?, finished_at = strftime('%s', 'now'), \
+         updated_at = strftime('%s', 'now') \
+         WHERE app_id = ? AND task_id = ? AND status NOT IN (?, ?, ?)",
+    )
+    .bind(status.as_str_name())
+    .bind(error)
+    .bind(key.app_id())
+    .bind(key.task_id())
+    .bind(TaskStatus::Succeeded.as_str_name())
+    .bind(TaskStatus::Failed.as_str_name())
+    .bind(TaskStatus::Cancelled.as_str_name())
+    .execute(conn)
+    .await?;
+
+    Ok(res.rows_affected() > 0)
+}
+
+// check the app in a transaction which has written, so the app couldn't be deleted until it ends
+async fn app_exists(conn: &mut SqliteConnection, app_id: &str) -> Result<bool> {
+    let row = sqlx::query("SELECT 1 FROM apps WHERE app_id = ?")
+        .bind(app_id)
+        .fetch_optional(conn)
+        .await?;
+
+    Ok(row.is_some())
+}
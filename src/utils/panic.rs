@@ -0,0 +1,9 @@
+use std::any::Any;
+
+// extract the message of a caught panic
+pub fn panic_message(err: Box<dyn Any + Send>) -> String {
+    err.downcast_ref::<&str>()
+        .map(|msg| msg.to_string())
+        .or_else(|| err.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "unknown panic".to_string())
+}